@@ -0,0 +1,166 @@
+//! Comparing the packages with the report of a previous run.
+
+use crate::error::UsageError;
+use crate::{AutoTagArgs, OutputFormat, Package};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// A package in the JSON report of a previous run.
+#[derive(serde::Deserialize)]
+struct BaselinePackage {
+    ecosystem: String,
+    manifest: PathBuf,
+    name: Option<String>,
+    version: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct BaselineReport {
+    packages: Vec<BaselinePackage>,
+}
+
+/// A package that was added, changed or removed since the baseline.
+#[derive(serde::Serialize)]
+struct Change {
+    ecosystem: String,
+    manifest: PathBuf,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct Delta {
+    added: Vec<Change>,
+    changed: Vec<Change>,
+    removed: Vec<Change>,
+}
+
+/// Prints the packages that were added, removed or changed their version
+/// compared to the report written by a previous run with `--output json`.
+pub(crate) fn compare(
+    args: &AutoTagArgs,
+    path: &Path,
+    packages: &[Package],
+) -> Result<(), anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| UsageError(format!("failed to read {:?}: {}", path, err)))?;
+    let report: BaselineReport = serde_json::from_str(&contents)
+        .map_err(|err| UsageError(format!("invalid baseline {:?}: {}", path, err)))?;
+
+    // Skipped manifests without a package are not part of the baseline.
+    let mut previous: BTreeMap<(String, PathBuf), BaselinePackage> = report
+        .packages
+        .into_iter()
+        .filter(|package| package.name.is_some())
+        .map(|package| {
+            (
+                (package.ecosystem.clone(), normalize(&package.manifest)),
+                package,
+            )
+        })
+        .collect();
+
+    let mut delta = Delta {
+        added: Vec::new(),
+        changed: Vec::new(),
+        removed: Vec::new(),
+    };
+
+    for package in packages {
+        let key = (
+            package.ecosystem.name().to_string(),
+            normalize(&package.manifest),
+        );
+        let tag = package.tag_name(args);
+
+        match previous.remove(&key) {
+            Some(old) if old.version.as_deref() == Some(package.version.as_str()) => {}
+            old => {
+                let change = Change {
+                    ecosystem: key.0,
+                    manifest: package.manifest.clone(),
+                    name: package.name.clone(),
+                    previous_version: old.as_ref().and_then(|old| old.version.clone()),
+                    version: Some(package.version.clone()),
+                    previous_tag: old.as_ref().and_then(|old| old.tag.clone()),
+                    tag: Some(tag),
+                };
+
+                match old {
+                    Some(_) => delta.changed.push(change),
+                    None => delta.added.push(change),
+                }
+            }
+        }
+    }
+
+    for ((ecosystem, _), old) in previous {
+        delta.removed.push(Change {
+            ecosystem,
+            manifest: old.manifest,
+            name: old.name.unwrap_or_default(),
+            previous_version: old.version,
+            version: None,
+            previous_tag: old.tag,
+            tag: None,
+        });
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            for change in &delta.added {
+                args.print_output(format!(
+                    "added {} {} ({})",
+                    change.name,
+                    change.version.as_deref().unwrap_or_default(),
+                    change.tag.as_deref().unwrap_or_default(),
+                ))?;
+            }
+
+            for change in &delta.changed {
+                args.print_output(format!(
+                    "changed {} {} -> {} ({})",
+                    change.name,
+                    change.previous_version.as_deref().unwrap_or("none"),
+                    change.version.as_deref().unwrap_or_default(),
+                    change.tag.as_deref().unwrap_or_default(),
+                ))?;
+            }
+
+            for change in &delta.removed {
+                args.print_output(format!(
+                    "removed {} {}",
+                    change.name,
+                    change.previous_version.as_deref().unwrap_or("none"),
+                ))?;
+            }
+
+            args.print_output(format!(
+                "{} added, {} changed, {} removed",
+                delta.added.len(),
+                delta.changed.len(),
+                delta.removed.len()
+            ))?;
+        }
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Csv => args.print_data(&delta)?,
+    }
+
+    Ok(())
+}
+
+/// Removes `.` components, so that manifests found in the working directory
+/// and in trees are the same.
+fn normalize(manifest: &Path) -> PathBuf {
+    manifest
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
@@ -0,0 +1,48 @@
+//! The `.auto-tag.toml` configuration file.
+
+use crate::error::UsageError;
+use crate::Ecosystem;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The configuration file, read from the current directory.
+pub(crate) const CONFIG_FILE: &str = ".auto-tag.toml";
+
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    /// The dotted paths of the keys that enable tagging in the manifests of
+    /// each ecosystem, by ecosystem name.
+    #[serde(default)]
+    enable_keys: HashMap<String, String>,
+}
+
+impl Config {
+    /// Reads the configuration file, or returns the defaults if there is none.
+    pub(crate) fn load() -> Result<Self, anyhow::Error> {
+        let path = Path::new(CONFIG_FILE);
+
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+
+        let config: Config = toml::from_str(&contents)
+            .map_err(|err| UsageError(format!("invalid {CONFIG_FILE}: {err}")))?;
+
+        for ecosystem in config.enable_keys.keys() {
+            ecosystem
+                .parse::<Ecosystem>()
+                .map_err(|err| UsageError(format!("invalid {CONFIG_FILE}: {err}")))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the configured key that enables tagging in manifests of the
+    /// ecosystem.
+    pub(crate) fn enable_key(&self, ecosystem: Ecosystem) -> Option<&str> {
+        self.enable_keys.get(ecosystem.name()).map(String::as_str)
+    }
+}
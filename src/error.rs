@@ -0,0 +1,116 @@
+//! Errors and the exit codes they map to.
+
+/// Some packages could not be processed.
+pub(crate) const EXIT_FAILED: i32 = 1;
+/// The command line arguments or the configuration are invalid.
+pub(crate) const EXIT_USAGE: i32 = 2;
+/// The repository could not be opened or a git operation failed.
+pub(crate) const EXIT_GIT: i32 = 3;
+/// A network operation such as pushing failed.
+pub(crate) const EXIT_NETWORK: i32 = 4;
+
+/// Returns the exit code for an error that aborted the run.
+pub(crate) fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.is::<NetworkError>() {
+        EXIT_NETWORK
+    } else if err.is::<UsageError>() {
+        EXIT_USAGE
+    } else if err.is::<git2::Error>() || matches!(err.downcast_ref(), Some(AutoTagError::Git(_))) {
+        EXIT_GIT
+    } else {
+        EXIT_FAILED
+    }
+}
+
+/// An invalid command line argument or configuration.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub(crate) struct UsageError(pub(crate) String);
+
+/// A failed network operation.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub(crate) struct NetworkError(pub(crate) String);
+
+/// Some packages could not be processed.
+#[derive(Debug, thiserror::Error)]
+#[error("{0} package(s) failed")]
+pub(crate) struct PackagesFailed(pub(crate) usize);
+
+/// An error that occurred while reading a package from its manifest.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum AutoTagError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The manifest is not valid TOML or JSON.
+    #[error(transparent)]
+    Parse(Box<dyn std::error::Error + Send + Sync>),
+    #[error("{0} not found")]
+    MissingField(&'static str),
+    /// A field of the manifest has the wrong type.
+    #[error("{0} must be a string, found {1}")]
+    InvalidType(&'static str, &'static str),
+    #[error("invalid version {0:?}: {1}")]
+    InvalidVersion(String, semver::Error),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+    /// The external detector failed or printed invalid output.
+    #[error("{0}")]
+    Detector(String),
+}
+
+impl AutoTagError {
+    /// Returns the kind of the error as it appears in reports.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            AutoTagError::Io(_) => "io",
+            AutoTagError::Parse(_) => "parse",
+            AutoTagError::MissingField(_) => "missing-field",
+            AutoTagError::InvalidType(..) => "invalid-type",
+            AutoTagError::InvalidVersion(..) => "invalid-version",
+            AutoTagError::Git(_) => "git",
+            AutoTagError::Detector(_) => "detector",
+        }
+    }
+
+    /// Returns the kind of the error if it is known.
+    pub(crate) fn kind_of(err: &anyhow::Error) -> Option<&'static str> {
+        if let Some(err) = err.downcast_ref::<AutoTagError>() {
+            return Some(err.kind());
+        }
+
+        if err.is::<git2::Error>() {
+            return Some("git");
+        }
+
+        if err.is::<std::io::Error>() {
+            return Some("io");
+        }
+
+        None
+    }
+}
+
+impl From<toml::de::Error> for AutoTagError {
+    fn from(err: toml::de::Error) -> Self {
+        AutoTagError::Parse(err.into())
+    }
+}
+
+impl From<serde_json::Error> for AutoTagError {
+    fn from(err: serde_json::Error) -> Self {
+        AutoTagError::Parse(err.into())
+    }
+}
+
+impl From<json5::Error> for AutoTagError {
+    fn from(err: json5::Error) -> Self {
+        AutoTagError::Parse(err.into())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for AutoTagError {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        AutoTagError::Parse(err.into())
+    }
+}
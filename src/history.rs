@@ -0,0 +1,158 @@
+//! Walking the commit history of the repository.
+
+use anyhow::anyhow;
+use git2::{Commit, DiffOptions, Oid, Repository};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+/// Returns the directory containing the manifest relative to the root of the
+/// repository, with `/` as separator.
+///
+/// Manifests read from a tree (`in_tree`) already have paths relative to the
+/// root, which may not exist in the working directory.
+///
+/// Returns `None` if the manifest is at the root of the repository.
+pub(crate) fn package_dir(
+    repo: &Repository,
+    manifest: &Path,
+    in_tree: bool,
+) -> Result<Option<String>, anyhow::Error> {
+    let dir = manifest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    let components: Vec<String> = match repo.workdir().filter(|_| !in_tree) {
+        Some(workdir) => {
+            let workdir = workdir.canonicalize()?;
+            let dir = dir.canonicalize()?;
+
+            dir.strip_prefix(&workdir)
+                .map_err(|_| anyhow!("{:?} is outside of the repository", manifest))?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect()
+        }
+        None => dir
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect(),
+    };
+
+    if components.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(components.join("/")))
+}
+
+/// Returns the commits reachable from `target` but not from `since` that
+/// change files within `dir`, newest first.
+///
+/// All commits are considered if `dir` is `None`. With `first_parent`, only
+/// the first parents of merge commits are followed.
+pub(crate) fn commits_touching<'r>(
+    repo: &'r Repository,
+    target: Oid,
+    since: Option<Oid>,
+    first_parent: bool,
+    dir: Option<&str>,
+) -> Result<Vec<Commit<'r>>, anyhow::Error> {
+    walk(repo, target, since, first_parent, |commit| {
+        touches(repo, commit, dir)
+    })
+}
+
+/// Returns the commits reachable from `target` but not from `since` that
+/// belong to the package: those whose conventional commit scope is mapped to
+/// it in `scopes`, and those without a mapped scope that change files within
+/// `dir`.
+pub(crate) fn package_commits<'r>(
+    repo: &'r Repository,
+    target: Oid,
+    since: Option<Oid>,
+    first_parent: bool,
+    dir: Option<&str>,
+    scopes: &HashMap<String, String>,
+    package: &str,
+) -> Result<Vec<Commit<'r>>, anyhow::Error> {
+    walk(repo, target, since, first_parent, |commit| {
+        let scoped =
+            scope(commit.message().unwrap_or_default()).and_then(|scope| scopes.get(scope));
+
+        match scoped {
+            Some(scoped) => Ok(scoped == package),
+            None => touches(repo, commit, dir),
+        }
+    })
+}
+
+/// Returns the scope of a conventional commit message, e.g. `foo` for
+/// `feat(foo): ...`.
+pub(crate) fn scope(message: &str) -> Option<&str> {
+    let subject = message.lines().next()?;
+    let (header, _) = subject.split_once(':')?;
+    let (_, scope) = header.split_once('(')?;
+    let (scope, _) = scope.split_once(')')?;
+
+    Some(scope.trim()).filter(|scope| !scope.is_empty())
+}
+
+/// Returns the commits reachable from `target` but not from `since` that are
+/// kept by `keep`, newest first.
+fn walk<'r>(
+    repo: &'r Repository,
+    target: Oid,
+    since: Option<Oid>,
+    first_parent: bool,
+    mut keep: impl FnMut(&Commit<'r>) -> Result<bool, anyhow::Error>,
+) -> Result<Vec<Commit<'r>>, anyhow::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(target)?;
+
+    if first_parent {
+        revwalk.simplify_first_parent()?;
+    }
+
+    if let Some(since) = since {
+        revwalk.hide(since)?;
+    }
+
+    let mut commits = Vec::new();
+
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+
+        if keep(&commit)? {
+            commits.push(commit);
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Returns whether the commit changes files within `dir` compared to its
+/// first parent.
+fn touches(repo: &Repository, commit: &Commit, dir: Option<&str>) -> Result<bool, anyhow::Error> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => return Ok(true),
+    };
+
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+
+    let mut options = DiffOptions::new();
+    options.pathspec(dir);
+
+    let diff = repo.diff_tree_to_tree(
+        parent_tree.as_ref(),
+        Some(&commit.tree()?),
+        Some(&mut options),
+    )?;
+
+    Ok(diff.deltas().len() > 0)
+}
@@ -0,0 +1,78 @@
+//! Enabling tagging in package manifests.
+
+use crate::sources::{strip_bom, SOURCES};
+use crate::AutoTagArgs;
+use anyhow::anyhow;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(clap::Args)]
+pub(crate) struct InitArgs {
+    /// The manifests to enable tagging in.
+    #[clap(required_unless_present = "all-in")]
+    manifests: Vec<PathBuf>,
+    /// Enable tagging in every recognized manifest within the directory.
+    #[clap(long, value_name = "DIR")]
+    all_in: Option<PathBuf>,
+}
+
+/// Enables tagging in the given manifests.
+pub(crate) fn init(args: &AutoTagArgs, init_args: &InitArgs) -> Result<(), anyhow::Error> {
+    let mut failed = 0;
+
+    for manifest in &init_args.manifests {
+        if let Err(err) = enable(args, manifest, true) {
+            args.print(format!("failed to enable {:?}: {}", manifest, err));
+            failed += 1;
+        }
+    }
+
+    if let Some(dir) = &init_args.all_in {
+        for entry in WalkDir::new(dir) {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(err) => {
+                    args.print(format!("cannot access file: {}", err));
+                    continue;
+                }
+            };
+
+            // Manifests without a package, such as workspace roots, are
+            // expected within directories.
+            if let Err(err) = enable(args, entry.path(), false) {
+                args.print(format!("skipping {:?}: {}", entry.path(), err));
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("failed to enable {} manifest(s)", failed));
+    }
+
+    Ok(())
+}
+
+/// Enables tagging in the manifest, unrecognized files are an error only if
+/// `explicit` is set.
+fn enable(args: &AutoTagArgs, path: &Path, explicit: bool) -> Result<(), anyhow::Error> {
+    let source = match SOURCES.iter().find(|source| source.matches(path)) {
+        Some(source) => source,
+        None if explicit => return Err(anyhow!("unsupported manifest")),
+        None => return Ok(()),
+    };
+
+    let contents = std::fs::read_to_string(path)?;
+    let stripped = strip_bom(&contents);
+
+    match source.enable(stripped)? {
+        Some(enabled) => {
+            // Keep the byte order mark if there was one.
+            let bom = &contents[..contents.len() - stripped.len()];
+            std::fs::write(path, format!("{bom}{enabled}"))?;
+            args.print(format!("enabled tagging in {:?}", path));
+        }
+        None => args.print(format!("tagging is already enabled in {:?}", path)),
+    }
+
+    Ok(())
+}
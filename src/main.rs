@@ -1,221 +1,4379 @@
 use anyhow::anyhow;
 use clap::Parser;
-use git2::{Oid, Repository, Signature};
+use error::{AutoTagError, NetworkError, PackagesFailed, UsageError};
+use git2::{
+    BranchType, Commit, Cred, CredentialType, Direction, ErrorCode, Oid, ProxyOptions, PushOptions,
+    RemoteCallbacks, Repository, Signature,
+};
+use globset::GlobBuilder;
+use sources::{Detector, Extracted, PackageSource, ReadFile, SOURCES};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Instant;
 use walkdir::WalkDir;
 
-/// Automatically create git tags for Cargo (Cargo.toml), JavaScript (package.json), and Python (pyproject.toml) packages.
+mod baseline;
+mod config;
+mod error;
+mod history;
+mod init;
+mod sources;
+mod suggest;
+
+/// Automatically create git tags for Cargo (Cargo.toml), JavaScript (package.json), Python (pyproject.toml), and Deno (deno.json) packages.
 #[derive(clap::Parser)]
+#[clap(subcommand_negates_reqs = true)]
 struct AutoTagArgs {
+    #[clap(subcommand)]
+    command: Option<Command>,
     /// Print the tags to be created but do not create them.
     #[clap(long)]
     dry_run: bool,
+    /// Also print the planned tags as GitHub Actions annotations with
+    /// `github`, shown next to the manifests in pull requests.
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "FORMAT",
+        default_value = "plain",
+        requires = "dry-run"
+    )]
+    dry_run_format: DryRunFormat,
     /// The commit SHA to create the tag for.
-    /// 
+    ///
     /// Uses HEAD by default.
     #[clap(long)]
     commit: Option<String>,
+    /// Tag the merge base of HEAD and the given revision instead of HEAD,
+    /// such as the target branch of a pull request whose HEAD is a merge
+    /// commit.
+    ///
+    /// Manifests are read from the merge base as well.
+    #[clap(long, value_name = "BASE", conflicts_with_all = &["commit", "from-tree", "staged"])]
+    merge_base: Option<String>,
+    /// Do not warn when the commit given via `--commit` is not HEAD.
+    #[clap(long)]
+    no_head_check: bool,
+    /// Replace existing release tags or branches that differ from the ones
+    /// that would be created.
+    #[clap(long)]
+    force: bool,
+    /// Refuse to create more than the given number of tags, unless `--force`
+    /// is given.
+    #[clap(long, value_name = "N")]
+    max_tags: Option<usize>,
+    /// Keep the messages of annotated tags replaced with `--force`.
+    #[clap(long, requires = "force")]
+    preserve_message: bool,
+    /// Create lightweight tags without a message.
+    ///
+    /// Defaults to the `auto-tag.tag-type` git config, or annotated tags.
+    #[clap(long, conflicts_with = "annotated")]
+    lightweight: bool,
+    /// Create annotated tags, regardless of the `auto-tag.tag-type` git
+    /// config.
+    #[clap(long)]
+    annotated: bool,
+    /// Delete the release tags of packages that are no longer found.
+    ///
+    /// Only tags named by the templates and prefixes given on the command
+    /// line are considered. Asks for confirmation unless `--yes` is given.
+    ///
+    /// The whole repository must be searched, so that no package is missed.
+    #[clap(long, conflicts_with_all = &["no-walk", "cargo-workspace"])]
+    prune: bool,
+    /// Delete the tags found with `--prune` without asking for confirmation.
+    #[clap(long, requires = "prune")]
+    yes: bool,
+    #[clap(long, required_unless_present = "tagger-from-commit")]
+    git_user_email: Option<String>,
+    #[clap(long, required_unless_present = "tagger-from-commit")]
+    git_user_name: Option<String>,
+    /// Use the committer of the tagged commit as the tagger, including the
+    /// time. `--git-user-name` and `--git-user-email` take precedence.
+    #[clap(long)]
+    tagger_from_commit: bool,
+    /// Override the version of a package, in the form `<package>=<version>`.
+    ///
+    /// Can be given multiple times.
+    #[clap(
+        long,
+        global = true,
+        value_name = "PACKAGE=VERSION",
+        parse(try_from_str = parse_set_version)
+    )]
+    set_version: Vec<(String, String)>,
+    /// The template used for tag names, with optional `{prefix}`, `{name}` and `{version}` placeholders.
+    ///
+    /// Environment variables can be used as `${VAR}`.
+    ///
+    /// Can be prefixed with an ecosystem (`cargo`, `npm`, `python`, `deno` or `external`) in the form
+    /// `<ecosystem>=<template>` to only apply to the given ecosystem.
+    ///
+    /// Defaults to `{prefix}{name}-{version}`.
+    #[clap(
+        long,
+        global = true,
+        value_name = "[ECOSYSTEM=]TEMPLATE",
+        parse(try_from_str = parse_ecosystem_value)
+    )]
+    tag_template: Vec<(Option<Ecosystem>, String)>,
+    /// The prefix substituted for `{prefix}` in tag templates.
+    ///
+    /// Can be prefixed with an ecosystem in the form `<ecosystem>=<prefix>`
+    /// to only apply to the given ecosystem.
+    ///
+    /// Defaults to `release-`.
+    #[clap(
+        long,
+        global = true,
+        value_name = "[ECOSYSTEM=]PREFIX",
+        parse(try_from_str = parse_ecosystem_value)
+    )]
+    tag_prefix: Vec<(Option<Ecosystem>, String)>,
+    /// The prefix of versions in tag names, such as `v` for
+    /// `release-foo-v1.0.0`.
+    ///
+    /// Can be prefixed with an ecosystem in the form `<ecosystem>=<prefix>`
+    /// to only apply to the given ecosystem, which takes precedence over a
+    /// prefix given without one.
+    #[clap(
+        long,
+        global = true,
+        value_name = "[ECOSYSTEM=]PREFIX",
+        parse(try_from_str = parse_ecosystem_value)
+    )]
+    version_prefix: Vec<(Option<Ecosystem>, String)>,
+    /// Prefix tag names with the committer date of the tagged commit in the
+    /// `YYYYMMDD` format, e.g. `20240601-release-foo-1.0.0`.
+    #[clap(long, global = true)]
+    date_prefix: bool,
+    /// A TOML or JSON file mapping tag names to the names to use instead,
+    /// e.g. those of an earlier naming scheme.
+    #[clap(long, global = true, value_name = "PATH")]
+    rename_map: Option<PathBuf>,
+    /// Replace environment variables in tag templates that are not set with
+    /// nothing instead of failing.
+    #[clap(long, global = true)]
+    allow_unset_env: bool,
+    /// Drop the build metadata (e.g. `+build.5`) from versions in tags.
+    #[clap(long, global = true)]
+    strip_build_metadata: bool,
+    /// Drop the `@scope/` part of scoped npm package names in tags.
+    #[clap(long, global = true)]
+    strip_scope: bool,
+    /// Only tag the packages with the given names, whether or not tagging is
+    /// enabled in their manifests.
+    ///
+    /// Can be given multiple times or as a comma-separated list. Defaults to
+    /// the `AUTO_TAG_ENABLED` environment variable.
+    #[clap(long, global = true, value_name = "NAMES", use_value_delimiter = true)]
+    enabled_packages: Vec<String>,
+    /// Only tag packages whose enable key is set to the given string, such as
+    /// `production`, instead of `true`.
+    #[clap(long, global = true, value_name = "VALUE")]
+    enable_value: Option<String>,
+    /// Tag the version the given dist-tag (e.g. `latest`) of npm packages
+    /// points to in the registry instead of the version in `package.json`.
+    ///
+    /// Uses `npm view`, and falls back to the version in `package.json`
+    /// with a warning if it fails.
+    #[clap(long, global = true, value_name = "TAG")]
+    npm_version_from_dist_tag: Option<String>,
+    /// Use the names of Python packages in tags as they are written, instead
+    /// of normalizing them as described in PEP 503 (`My_Package` becomes
+    /// `my-package`).
+    #[clap(long, global = true)]
+    no_python_normalize_names: bool,
+    /// What the `/` in scoped npm package names is replaced with in tags.
+    #[clap(
+        long,
+        global = true,
+        default_value = "__",
+        parse(try_from_str = parse_ref_fragment)
+    )]
+    scope_separator: String,
+    /// What the `@` in scoped npm package names is replaced with in tags.
+    #[clap(
+        long,
+        global = true,
+        default_value = "",
+        parse(try_from_str = parse_ref_fragment)
+    )]
+    scope_prefix: String,
+    /// An external command used to detect packages in files matching `--detector-glob`.
+    ///
+    /// The command is called with the path of the file, and must print the
+    /// package as JSON in the form `{"name": ..., "version": ..., "enabled": ...}`.
+    #[clap(long, global = true, requires = "detector-glob")]
+    detector: Option<PathBuf>,
+    /// The glob matching files passed to `--detector`.
+    ///
+    /// Globs without a `/` are matched against file names only.
+    #[clap(long, global = true, requires = "detector")]
+    detector_glob: Option<String>,
+    /// Read manifests from the tree of the given revision instead of the
+    /// working directory.
+    #[clap(long, global = true, value_name = "REVSPEC")]
+    from_tree: Option<String>,
+    /// Read manifests from the index instead of the working directory, e.g.
+    /// in pre-commit hooks.
+    ///
+    /// Tags can only be planned with `--dry-run` in this mode.
+    #[clap(long, global = true, conflicts_with = "from-tree")]
+    staged: bool,
+    /// Allow comments and trailing commas in `package.json` files.
+    #[clap(long, global = true)]
+    allow_jsonc: bool,
+    /// The number of hex digits commit hashes are abbreviated to in
+    /// messages.
+    ///
+    /// Defaults to the shortest unique abbreviation of at least
+    /// `core.abbrev` digits, like git does.
+    #[clap(
+        long,
+        global = true,
+        value_name = "N",
+        parse(try_from_str = parse_abbrev)
+    )]
+    abbrev: Option<usize>,
+    /// Read the version from a `VERSION` file next to manifests that have
+    /// none.
+    #[clap(long, global = true)]
+    version_file_fallback: bool,
+    /// Fail Cargo, npm and Deno packages whose version is not valid semver,
+    /// as those ecosystems require.
+    #[clap(long, global = true)]
+    strict_versions: bool,
+    /// Only process the members of the Cargo workspace with the given root,
+    /// instead of searching the paths.
+    #[clap(long, global = true, value_name = "DIR")]
+    cargo_workspace: Option<PathBuf>,
+    /// Treat the paths as manifest files instead of searching them as
+    /// directories.
+    #[clap(long, global = true, conflicts_with = "cargo-workspace")]
+    no_walk: bool,
+    /// Create a single tag for virtual Cargo workspaces with a
+    /// `workspace.package.version`, instead of one for every member that
+    /// inherits it.
+    #[clap(long, global = true)]
+    cargo_workspace_tag: bool,
+    /// Read the versions of Cargo packages from the `Cargo.lock` next to
+    /// their manifest if it has them, e.g. for applications.
+    #[clap(long, global = true)]
+    cargo_version_from_lock: bool,
+    /// Derive the versions of Python packages that get them from git, such
+    /// as with `setuptools_scm`, from their release tags.
+    #[clap(long, global = true)]
+    scm_version: bool,
+    /// Create branches instead of tags for releases.
+    #[clap(long, global = true)]
+    as_branch: bool,
+    /// Only tag the package of this ecosystem when a directory contains the
+    /// manifests of several ecosystems.
+    #[clap(long, global = true, value_name = "ECOSYSTEM")]
+    primary_ecosystem: Option<Ecosystem>,
+    /// Prefix the names of packages with their ecosystem when a directory
+    /// contains the manifests of several ecosystems, or when packages of
+    /// different ecosystems would get the same tag.
+    #[clap(long, global = true, conflicts_with = "primary-ecosystem")]
+    qualify_with_ecosystem: bool,
+    /// Prefix the names of packages with the path of their directory relative
+    /// to the root of the repository, e.g. `release-crates/foo/foo-1.0.0`.
+    #[clap(long, global = true)]
+    qualify_with_path: bool,
+    /// Only use the last N components of the path with `--qualify-with-path`.
+    #[clap(long, global = true, value_name = "N", requires = "qualify-with-path")]
+    path_qualify_depth: Option<usize>,
+    /// Add the subjects of the commits changing the package since its
+    /// previous release to the tag message.
+    #[clap(long, conflicts_with = "message-file")]
+    notes_from_log: bool,
+    /// Attribute commits with the given conventional commit scope to a
+    /// package for release notes and suggested versions, in the form
+    /// `<scope>=<package>`.
+    ///
+    /// Scopes that equal the name of a package are attributed to it by
+    /// default. Can be given multiple times.
+    #[clap(
+        long,
+        global = true,
+        value_name = "SCOPE=PACKAGE",
+        parse(try_from_str = parse_scope_map)
+    )]
+    scope_map: Vec<(String, String)>,
+    /// Only follow the first parents of merge commits when looking for the
+    /// commits of a package, like `git log --first-parent`.
+    #[clap(long, global = true)]
+    first_parent: bool,
+    /// Use the contents of the given file as the message of every tag.
+    #[clap(long, value_name = "PATH")]
+    message_file: Option<PathBuf>,
+    /// Add the CI system, the URL of its run and the tagged commit to the tag
+    /// message as a `Provenance:` line with a JSON object.
+    ///
+    /// Nothing is added when not running in a known CI system.
+    #[clap(long)]
+    provenance: bool,
+    /// Create a single tag with the given name for all packages instead of
+    /// one tag per package.
+    ///
+    /// The tag message lists every package and its version.
+    #[clap(long, value_name = "TAG", conflicts_with = "as-branch")]
+    combined_tag: Option<String>,
+    /// Refuse to create tags unless HEAD is on the given branch.
+    #[clap(long, value_name = "BRANCH")]
+    only_on_branch: Option<String>,
+    /// Push created tags to the remotes.
+    #[clap(long)]
+    push: bool,
+    /// The remotes to push to.
+    ///
+    /// Can be given multiple times or as a comma-separated list.
+    #[clap(long, default_value = "origin", use_value_delimiter = true)]
+    remote: Vec<String>,
+    /// Whether tags are pushed one by one right after they are created, or
+    /// all at once at the end.
+    ///
+    /// With `per-tag`, the remaining tags are still created and pushed if
+    /// pushing a tag fails. Tags are always pushed at once with `--atomic`.
+    #[clap(long, arg_enum, value_name = "MODE", default_value = "batch")]
+    push_mode: PushMode,
+    /// Also consider tags that only exist on the remotes as existing.
+    #[clap(long)]
+    check_remote: bool,
+    /// Warn about manifests that do not have the key that enables tagging,
+    /// e.g. because of a typo.
+    #[clap(long)]
+    warn_unconfigured: bool,
+    /// Fail if none of the manifests of an ecosystem have the key that
+    /// enables tagging.
+    #[clap(long)]
+    require_config: bool,
+    /// Skip packages if any tag of the commit has the message of a release of
+    /// the same version, even if its name differs.
+    #[clap(long)]
+    idempotent: bool,
+    /// Skip packages without any commits changing their directory since
+    /// their latest release.
+    #[clap(long)]
+    since_last_tag: bool,
+    /// Skip packages whose version in the manifest is the same in the
+    /// commit being tagged and in `--version-baseline`.
+    #[clap(long)]
+    version_changed_only: bool,
+    /// The revision to compare versions with for `--version-changed-only`,
+    /// the first parent of the commit being tagged by default.
+    #[clap(long, value_name = "REVSPEC", requires = "version-changed-only")]
+    version_baseline: Option<String>,
+    /// Consider tags that only differ in case as existing, for
+    /// case-insensitive file systems.
+    #[clap(long)]
+    ignore_case: bool,
+    /// Create either all tags or none of them.
+    ///
+    /// Nothing is tagged if any manifest fails to be read, and tags created
+    /// before a failure are removed again. Tags are only pushed after all of
+    /// them were created.
+    #[clap(long)]
+    atomic: bool,
+    /// Give up on network operations after the given number of seconds.
+    ///
+    /// 0 disables the timeout.
+    #[clap(long, value_name = "SECS", default_value = "30")]
+    network_timeout: u64,
+    /// The proxy used for network operations.
+    ///
+    /// Defaults to the `http.proxy` git config or the `HTTPS_PROXY`,
+    /// `HTTP_PROXY` and `NO_PROXY` environment variables.
+    #[clap(long, value_name = "URL")]
+    proxy: Option<String>,
+    /// Attach a git note with the given text to the tagged commit.
+    #[clap(long, conflicts_with = "note-from")]
+    note: Option<String>,
+    /// Attach a git note with the contents of the given file to the tagged commit.
+    #[clap(long)]
+    note_from: Option<PathBuf>,
+    /// The notes ref used for notes.
+    #[clap(long, default_value = "refs/notes/releases")]
+    notes_ref: String,
+    /// What to do if the commit already has a note.
+    #[clap(long, arg_enum, default_value = "append")]
+    note_mode: NoteMode,
+    /// The output format.
+    #[clap(long, global = true, arg_enum, default_value = "text")]
+    output: OutputFormat,
+    /// Write the output to the given file instead of stdout, other messages
+    /// are printed to stderr.
+    #[clap(long, global = true, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+    /// Load environment variables such as `GIT_TOKEN` from `.env`.
+    ///
+    /// Variables that are already set are not overridden.
+    #[clap(long, global = true)]
+    load_env: bool,
+    /// Load environment variables from the given file instead of `.env`.
+    #[clap(long, global = true, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+    /// Exit with 1 in dry-run mode if any tags would be created.
+    #[clap(long, requires = "dry-run")]
+    exit_code: bool,
+    /// Only print the summary of the run.
     #[clap(long)]
-    git_user_email: String,
+    summary_only: bool,
+    /// Do not print a message for every package that is skipped because its
+    /// release tag already exists.
     #[clap(long)]
-    git_user_name: String,
-    /// Directories to search for packages.
+    quiet_skips: bool,
+    /// POST the results as JSON to the given URL after the run.
+    ///
+    /// Uses `curl`. Failing to notify only prints a warning.
+    #[clap(long, value_name = "URL")]
+    notify_webhook: Option<String>,
+    /// When to notify the webhook given via `--notify-webhook`.
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "WHEN",
+        default_value = "created",
+        requires = "notify-webhook"
+    )]
+    notify_on: NotifyOn,
+    /// Print how long each phase of the run took.
+    #[clap(long)]
+    timings: bool,
+    /// Write the results as a markdown table to the given file.
+    #[clap(long, value_name = "FILE")]
+    summary_markdown: Option<PathBuf>,
+    /// Append to the `--summary-markdown` file instead of overwriting it.
+    #[clap(long, requires = "summary-markdown")]
+    summary_append: bool,
+    /// Write the tagged packages with their tags and commit to the given file
+    /// as JSON, e.g. for auditing releases.
+    #[clap(long, value_name = "FILE")]
+    sbom_out: Option<PathBuf>,
+    /// List every package in the `--sbom-out` file, not only the tagged ones.
+    #[clap(long, requires = "sbom-out")]
+    sbom_all: bool,
+    /// Print the packages that were added, removed or changed their version
+    /// since the report of a previous run with `--output json`, instead of
+    /// creating tags.
+    #[clap(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+    /// Directories to search for packages, globs such as `packages/*` are
+    /// expanded.
+    #[clap(default_value = ".")]
+    paths: Vec<PathBuf>,
+    /// The contents of the `.auto-tag.toml` file.
+    #[clap(skip)]
+    config: config::Config,
+    /// The committer date of the tagged commit used with `--date-prefix`.
+    #[clap(skip)]
+    commit_date: String,
+    /// The working directory of the repository, or the repository itself if
+    /// it is bare.
+    #[clap(skip)]
+    repo_dir: PathBuf,
+    /// The contents of the `--rename-map` file.
+    #[clap(skip)]
+    renames: HashMap<String, String>,
+    #[clap(skip)]
+    workspace_cache: sources::WorkspaceCache,
+    /// The packages that commits with each scope are attributed to.
+    #[clap(skip)]
+    scopes: HashMap<String, String>,
+    /// The opened `--output-file`.
+    #[clap(skip)]
+    output_writer: RefCell<Option<std::fs::File>>,
+    /// Whether packages are read from manifests regardless of whether they
+    /// enable tagging, to find the packages that are disabled.
+    #[clap(skip)]
+    enable_all: Cell<bool>,
+}
+
+impl AutoTagArgs {
+    /// Prints a human-readable message.
+    ///
+    /// Messages go to stderr if results are printed in a machine-readable format
+    /// or to `--output-file`.
+    fn print(&self, message: impl std::fmt::Display) {
+        match self.output {
+            OutputFormat::Text if self.output_file.is_none() => println!("{message}"),
+            _ => eprintln!("{message}"),
+        }
+    }
+
+    /// Prints a line of the output, to `--output-file` if given.
+    fn print_output(&self, output: impl std::fmt::Display) -> Result<(), anyhow::Error> {
+        match &mut *self.output_writer.borrow_mut() {
+            Some(file) => writeln!(file, "{output}")?,
+            None => println!("{output}"),
+        }
+
+        Ok(())
+    }
+
+    /// Prints results in the machine-readable output format.
+    fn print_data(&self, data: &impl serde::Serialize) -> Result<(), anyhow::Error> {
+        match self.output {
+            OutputFormat::Yaml => self.print_output(serde_yaml::to_string(data)?.trim_end())?,
+            OutputFormat::Csv => {
+                return Err(UsageError("CSV output is only supported when tagging".into()).into())
+            }
+            OutputFormat::Text | OutputFormat::Json => {
+                self.print_output(serde_json::to_string_pretty(data)?)?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints a human-readable message about the progress of the run,
+    /// unless only the summary should be printed.
+    fn print_progress(&self, message: impl std::fmt::Display) {
+        if !self.summary_only {
+            self.print(message);
+        }
+    }
+
+    /// Prints a message about a package skipped because its release exists,
+    /// unless `--quiet-skips` is given.
+    fn print_existing(&self, message: impl std::fmt::Display) {
+        if !self.quiet_skips {
+            self.print_progress(message);
+        }
+    }
+
+    fn tag_template(&self, ecosystem: Ecosystem) -> &str {
+        ecosystem_value(&self.tag_template, ecosystem).unwrap_or(DEFAULT_TAG_TEMPLATE)
+    }
+
+    fn tag_prefix(&self, ecosystem: Ecosystem) -> &str {
+        ecosystem_value(&self.tag_prefix, ecosystem).unwrap_or(DEFAULT_TAG_PREFIX)
+    }
+
+    fn version_prefix(&self, ecosystem: Ecosystem) -> &str {
+        ecosystem_value(&self.version_prefix, ecosystem).unwrap_or_default()
+    }
+}
+
+const DEFAULT_TAG_TEMPLATE: &str = "{prefix}{name}-{version}";
+const DEFAULT_TAG_PREFIX: &str = "release-";
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Report packages whose current version has no release tag yet.
+    ///
+    /// This never creates any tags.
+    Diff(SearchArgs),
+    /// Suggest the next version of packages based on the conventional
+    /// commits since their latest release.
+    ///
+    /// This never modifies manifests or creates any tags.
+    Suggest(SearchArgs),
+    /// List the existing release tags with the package name and version
+    /// they are for, sorted by package.
+    ///
+    /// This never reads manifests or creates any tags.
+    ListExisting,
+    /// List the supported ecosystems, their manifests and the key that
+    /// enables tagging in them.
+    Ecosystems,
+    /// Enable tagging in package manifests.
+    Init(init::InitArgs),
+}
+
+#[derive(clap::Args)]
+struct SearchArgs {
+    /// Directories to search for packages, globs such as `packages/*` are
+    /// expanded.
     #[clap(default_value = ".")]
     paths: Vec<PathBuf>,
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let repo = match Repository::open(".") {
-        Ok(repo) => repo,
-        Err(e) => panic!("failed to open: {}", e),
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum NoteMode {
+    Append,
+    Replace,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+enum PushMode {
+    PerTag,
+    Batch,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+enum NotifyOn {
+    /// Only if any tags were created.
+    Created,
+    Always,
+    /// Only if any packages or the run failed.
+    Failure,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+enum DryRunFormat {
+    Plain,
+    Github,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Ecosystem {
+    Cargo,
+    Npm,
+    Python,
+    Deno,
+    /// Packages found by an external detector.
+    External,
+}
+
+impl Ecosystem {
+    fn name(self) -> &'static str {
+        match self {
+            Ecosystem::Cargo => "cargo",
+            Ecosystem::Npm => "npm",
+            Ecosystem::Python => "python",
+            Ecosystem::Deno => "deno",
+            Ecosystem::External => "external",
+        }
+    }
+}
+
+impl std::str::FromStr for Ecosystem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cargo" => Ok(Ecosystem::Cargo),
+            "npm" => Ok(Ecosystem::Npm),
+            "python" => Ok(Ecosystem::Python),
+            "deno" => Ok(Ecosystem::Deno),
+            "external" => Ok(Ecosystem::External),
+            _ => Err(anyhow!("unknown ecosystem {:?}", s)),
+        }
+    }
+}
+
+/// A package with automatic tagging enabled.
+struct Package {
+    ecosystem: Ecosystem,
+    /// The manifest file the package was found in.
+    manifest: PathBuf,
+    /// The package name as declared in the manifest.
+    package_name: String,
+    /// The package name as it appears in tags, either derived from the
+    /// package name or set explicitly in the manifest.
+    name: String,
+    version: String,
+    /// Tag template set in the package manifest.
+    template: Option<String>,
+    /// Tag prefix set in the package manifest.
+    prefix: Option<String>,
+}
+
+impl Package {
+    /// Returns the tag name for the package with the given version.
+    ///
+    /// Templates and prefixes set in the manifest take precedence over
+    /// ecosystem-specific ones given on the command line, which in turn take
+    /// precedence over the global ones.
+    fn tag_name_for(&self, args: &AutoTagArgs, version: &str) -> String {
+        self.render_tag_name(args, version).0
+    }
+
+    /// Renders the tag template, returning the tag name along with the
+    /// environment variables used in it that are not set.
+    fn render_tag_name(&self, args: &AutoTagArgs, version: &str) -> (String, Vec<String>) {
+        let template = self
+            .template
+            .as_deref()
+            .unwrap_or_else(|| args.tag_template(self.ecosystem));
+        let prefix = self
+            .prefix
+            .as_deref()
+            .unwrap_or_else(|| args.tag_prefix(self.ecosystem));
+
+        let version = format!("{}{version}", args.version_prefix(self.ecosystem));
+        let values = [("name", self.name.as_str()), ("version", version.as_str())];
+
+        let (prefix, unset_in_prefix) = render_template(prefix, &values);
+        let (tag_name, mut unset) = render_template(
+            template,
+            &[("prefix", prefix.as_str()), values[0], values[1]],
+        );
+        unset.extend(unset_in_prefix);
+
+        (tag_name, unset)
+    }
+
+    /// Makes sure that the environment variables used in the tag template
+    /// are set, and that the tag name is valid.
+    fn check_tag_name(&self, args: &AutoTagArgs) -> Result<(), anyhow::Error> {
+        if !args.allow_unset_env {
+            let (_, unset) = self.render_tag_name(args, &self.version);
+
+            if let Some(var) = unset.first() {
+                return Err(UsageError(format!(
+                    "the environment variable {var} used in the tag name of {} is not set",
+                    log_path(&self.manifest)
+                ))
+                .into());
+            }
+        }
+
+        let tag_name = self.tag_name(args);
+
+        if !git2::Reference::is_valid_name(&format!("refs/tags/{tag_name}")) {
+            return Err(UsageError(format!(
+                "{tag_name:?} of {} is not a valid tag name",
+                log_path(&self.manifest)
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn tag_name(&self, args: &AutoTagArgs) -> String {
+        let tag_name = self.computed_tag_name(args);
+
+        match args.renames.get(&tag_name) {
+            Some(renamed) => renamed.clone(),
+            None => tag_name,
+        }
+    }
+
+    /// Returns the tag name before applying `--rename-map`.
+    fn computed_tag_name(&self, args: &AutoTagArgs) -> String {
+        let version = match self.version.split_once('+') {
+            Some((version, _)) if args.strip_build_metadata => version,
+            _ => &self.version,
+        };
+
+        let tag_name = self.tag_name_for(args, version);
+
+        if args.date_prefix {
+            format!("{}-{tag_name}", args.commit_date)
+        } else {
+            tag_name
+        }
+    }
+}
+
+/// Packages found while searching for manifests.
+struct Discovery {
+    packages: Vec<Package>,
+    /// With `--prune`, the packages of manifests that do not enable tagging.
+    disabled: Vec<Package>,
+    /// Manifests that were skipped or could not be processed.
+    results: Vec<PackageResult>,
+    /// The ecosystems of manifests without the key that enables tagging.
+    unconfigured: Vec<Ecosystem>,
+    timings: Timings,
+}
+
+/// The time each phase of a run took, in seconds.
+#[derive(Default, serde::Serialize)]
+struct Timings {
+    /// Finding the manifests.
+    discovery: f64,
+    /// Reading packages from the manifests.
+    parsing: f64,
+    /// Creating tags and notes.
+    tagging: f64,
+    push: f64,
+}
+
+impl Timings {
+    fn describe(&self) -> String {
+        format!(
+            "discovery {:.3}s, parsing {:.3}s, tagging {:.3}s, push {:.3}s",
+            self.discovery, self.parsing, self.tagging, self.push
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Action {
+    /// The release tag was created, or would be in dry-run mode.
+    Created,
+    Skipped,
+    Failed,
+}
+
+/// Why no release tag was created for a package.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SkipReason {
+    /// A release tag of the version already exists.
+    AlreadyExists,
+    /// The release tag already exists and is identical to the one that would
+    /// be created.
+    Unchanged,
+    /// Tagging is not enabled in the manifest.
+    Disabled,
+    /// The manifest only defines a Cargo or uv workspace.
+    WorkspaceRoot,
+    /// The version is dynamic and could not be read from the file it is
+    /// defined in.
+    UnresolvedVersion,
+    /// The version is derived from release tags, but there are none.
+    NoReleaseTags,
+    /// Another package failed in an `--atomic` run.
+    Aborted,
+    /// With `--idempotent`, another tag of the version points at the commit.
+    AlreadyReleased,
+    /// With `--cargo-workspace-tag`, the version is tagged for the workspace.
+    WorkspaceMember,
+    /// The manifest does not have the key that enables tagging.
+    Unconfigured,
+    /// With `--since-last-tag`, nothing changed since the latest release.
+    UnchangedSinceLastRelease,
+    /// The Cargo manifest defines neither a package nor a workspace.
+    NoPackage,
+    /// With `--version-changed-only`, the version is the same as in the
+    /// baseline.
+    VersionUnchanged,
+}
+
+impl SkipReason {
+    fn describe(self) -> &'static str {
+        match self {
+            SkipReason::AlreadyExists => "a release tag already exists",
+            SkipReason::Unchanged => "the release tag is up to date",
+            SkipReason::Disabled => "tagging is not enabled",
+            SkipReason::WorkspaceRoot => "workspace root",
+            SkipReason::UnresolvedVersion => "the dynamic version cannot be resolved",
+            SkipReason::NoReleaseTags => "no release tags to derive the version from",
+            SkipReason::Aborted => "the atomic release was aborted",
+            SkipReason::AlreadyReleased => "the commit is already tagged with the version",
+            SkipReason::WorkspaceMember => "the version is tagged for the workspace",
+            SkipReason::Unconfigured => "tagging is not configured",
+            SkipReason::UnchangedSinceLastRelease => "no changes since the latest release",
+            SkipReason::NoPackage => "the manifest has no package",
+            SkipReason::VersionUnchanged => "the version did not change",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SkipReason::AlreadyExists => "already-exists",
+            SkipReason::Unchanged => "unchanged",
+            SkipReason::Disabled => "disabled",
+            SkipReason::WorkspaceRoot => "workspace-root",
+            SkipReason::UnresolvedVersion => "unresolved-version",
+            SkipReason::NoReleaseTags => "no-release-tags",
+            SkipReason::Aborted => "aborted",
+            SkipReason::AlreadyReleased => "already-released",
+            SkipReason::WorkspaceMember => "workspace-member",
+            SkipReason::Unconfigured => "unconfigured",
+            SkipReason::UnchangedSinceLastRelease => "unchanged-since-last-release",
+            SkipReason::NoPackage => "no-package",
+            SkipReason::VersionUnchanged => "version-unchanged",
+        }
+    }
+}
+
+/// The outcome of processing a single package.
+#[derive(serde::Serialize)]
+struct PackageResult {
+    ecosystem: Ecosystem,
+    manifest: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<SkipReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'static str>,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Created => "created",
+            Action::Skipped => "skipped",
+            Action::Failed => "failed",
+        }
+    }
+}
+
+impl PackageResult {
+    fn new(args: &AutoTagArgs, package: &Package, tag: Option<&str>, action: Action) -> Self {
+        PackageResult {
+            ecosystem: package.ecosystem,
+            manifest: package.manifest.clone(),
+            name: Some(package.name.clone()),
+            version: Some(package.version.clone()),
+            tag: Some(tag.map_or_else(|| package.tag_name(args), str::to_string)),
+            action,
+            reason: None,
+            error: None,
+            error_kind: None,
+        }
+    }
+
+    /// Returns the result of a manifest without a package to tag.
+    fn skipped(ecosystem: Ecosystem, manifest: &Path, reason: SkipReason) -> Self {
+        PackageResult {
+            ecosystem,
+            manifest: manifest.to_path_buf(),
+            name: None,
+            version: None,
+            tag: None,
+            action: Action::Skipped,
+            reason: Some(reason),
+            error: None,
+            error_kind: None,
+        }
+    }
+
+    fn failed(ecosystem: Ecosystem, manifest: &Path, error: &anyhow::Error) -> Self {
+        PackageResult {
+            ecosystem,
+            manifest: manifest.to_path_buf(),
+            name: None,
+            version: None,
+            tag: None,
+            action: Action::Failed,
+            reason: None,
+            error: None,
+            error_kind: None,
+        }
+        .with_error(error)
+    }
+
+    fn with_error(self, error: &anyhow::Error) -> Self {
+        PackageResult {
+            error: Some(error.to_string()),
+            error_kind: AutoTagError::kind_of(error),
+            ..self
+        }
+    }
+
+    fn with_reason(self, reason: SkipReason) -> Self {
+        PackageResult {
+            reason: Some(reason),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, serde::Serialize)]
+struct Summary {
+    created: usize,
+    skipped: usize,
+    failed: usize,
+    /// The number of skipped packages by reason.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    skip_reasons: BTreeMap<SkipReason, usize>,
+}
+
+impl Summary {
+    fn new(results: &[PackageResult]) -> Self {
+        let mut summary = Summary::default();
+
+        for result in results {
+            match result.action {
+                Action::Created => summary.created += 1,
+                Action::Skipped => summary.skipped += 1,
+                Action::Failed => summary.failed += 1,
+            }
+
+            if let Some(reason) = result.reason {
+                *summary.skip_reasons.entry(reason).or_default() += 1;
+            }
+        }
+
+        summary
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Report<'a> {
+    dry_run: bool,
+    packages: &'a [PackageResult],
+    summary: Summary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<&'a Timings>,
+}
+
+/// Creates the `--output-file` along with its parent directories, truncating
+/// it if it exists.
+fn create_output_file(path: &Path) -> Result<std::fs::File, std::io::Error> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::File::create(path)
+}
+
+/// Reads the tag names given via `--rename-map`, as TOML if the file has a
+/// `.toml` extension and as JSON otherwise.
+fn load_rename_map(path: &Path) -> Result<HashMap<String, String>, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| UsageError(format!("failed to read {:?}: {}", path, err)))?;
+
+    let renames: HashMap<String, String> = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&contents)
+            .map_err(|err| UsageError(format!("invalid {:?}: {}", path, err)))?
+    } else {
+        serde_json::from_str(&contents)
+            .map_err(|err| UsageError(format!("invalid {:?}: {}", path, err)))?
+    };
+
+    for (from, to) in &renames {
+        if !git2::Reference::is_valid_name(&format!("refs/tags/{to}")) {
+            return Err(UsageError(format!(
+                "invalid {:?}: {to:?} for {from:?} is not a valid tag name",
+                path
+            ))
+            .into());
+        }
+    }
+
+    Ok(renames)
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err:?}");
+        std::process::exit(error::exit_code(&err));
+    }
+}
+
+fn run() -> Result<(), anyhow::Error> {
+    let mut args = AutoTagArgs::parse();
+
+    if let Some(path) = &args.env_file {
+        dotenvy::from_path(path)
+            .map_err(|err| UsageError(format!("failed to load {:?}: {}", path, err)))?;
+    } else if args.load_env {
+        dotenvy::dotenv().map_err(|err| UsageError(format!("failed to load .env: {}", err)))?;
+    }
+
+    args.config = config::Config::load()?;
+
+    if let Some(path) = &args.output_file {
+        args.output_writer =
+            RefCell::new(Some(create_output_file(path).map_err(|err| {
+                UsageError(format!("failed to create {:?}: {}", path, err))
+            })?));
+    }
+
+    if args.npm_version_from_dist_tag.is_some() {
+        require_program("npm", "--npm-version-from-dist-tag")?;
+    }
+
+    if args.notify_webhook.is_some() && !args.dry_run {
+        require_program("curl", "--notify-webhook")?;
+    }
+
+    if args.enabled_packages.is_empty() {
+        if let Ok(names) = std::env::var("AUTO_TAG_ENABLED") {
+            args.enabled_packages = names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+
+    if let Some(path) = &args.rename_map {
+        args.renames = load_rename_map(path)?;
+    }
+
+    match &args.command {
+        Some(Command::Ecosystems) => return ecosystems(&args),
+        Some(Command::Init(init_args)) => return init::init(&args, init_args),
+        _ => {}
+    }
+
+    let repo = Repository::open(".")
+        .map_err(|err| anyhow::Error::new(err).context("failed to open the repository"))?;
+    args.repo_dir = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+
+    if args.date_prefix {
+        args.commit_date = commit_date(&target_commit(&args, &repo)?);
+    }
+
+    match &args.command {
+        Some(Command::Diff(diff_args)) => return diff(&args, diff_args, &repo),
+        Some(Command::Suggest(suggest_args)) => {
+            return suggest::suggest(&args, suggest_args, &repo);
+        }
+        Some(Command::ListExisting) => return list_existing(&args, &repo),
+        Some(Command::Ecosystems) | Some(Command::Init(_)) | None => {}
+    }
+
+    if args.staged && !args.dry_run {
+        return Err(UsageError(
+            "refusing to create tags for staged changes that are not committed yet, use --dry-run"
+                .into(),
+        )
+        .into());
+    }
+
+    if args.prune && args.paths != [PathBuf::from(".")] {
+        return Err(UsageError(
+            "refusing to prune when only some paths are searched, \
+             the packages in other paths would be considered removed"
+                .into(),
+        )
+        .into());
+    }
+
+    if args.prune && !args.yes && !args.dry_run && !std::io::stdin().is_terminal() {
+        return Err(UsageError(
+            "refusing to prune without confirmation, use --yes or run it on a terminal".into(),
+        )
+        .into());
+    }
+
+    if args.push || args.check_remote {
+        check_remotes(&args, &repo)?;
+    }
+
+    let Discovery {
+        packages,
+        disabled,
+        mut results,
+        unconfigured,
+        mut timings,
+    } = find_packages(&args, &repo, &args.paths)?;
+
+    if args.notes_from_log {
+        args.scopes = scope_packages(&args, &packages)?;
+    }
+
+    if args.combined_tag.is_none() {
+        for package in &packages {
+            let computed = package.computed_tag_name(&args);
+
+            if let Some(renamed) = args.renames.get(&computed) {
+                args.print_progress(format!(r#"renaming tag "{computed}" to "{renamed}""#));
+            }
+        }
+
+        check_tag_collisions(&args, &packages)?;
+    }
+
+    if let Some(path) = &args.baseline {
+        return baseline::compare(&args, path, &packages);
+    }
+
+    if args.require_config {
+        check_configured(&packages, &results, &unconfigured)?;
+    }
+
+    if let (Some(max_tags), None) = (args.max_tags, &args.combined_tag) {
+        let mut planned = 0;
+
+        for package in &packages {
+            if !release_exists(&args, &repo, &package.tag_name(&args))? {
+                planned += 1;
+            }
+        }
+
+        if planned > max_tags {
+            let message =
+                format!("{planned} tags would be created, more than --max-tags {max_tags}");
+
+            if args.dry_run {
+                args.print(format!("warning: {message}"));
+            } else if !args.force {
+                return Err(UsageError(format!(
+                    "refusing to create tags, {message} (use --force or a higher limit)"
+                ))
+                .into());
+            }
+        }
+    }
+
+    // Packages in manifests that failed to be read would look removed.
+    let prunable = results.iter().all(|result| result.action != Action::Failed);
+
+    if let Some(branch) = &args.only_on_branch {
+        if let Err(err) = check_branch(&repo, branch) {
+            if !args.dry_run {
+                return Err(UsageError(format!("refusing to create tags, {err}")).into());
+            }
+
+            args.print(format!("tags would not be created, {err}"));
+        }
+    }
+
+    warn_case_collisions(&args, &packages);
+
+    if args.commit.is_some() && !args.no_head_check && !packages.is_empty() {
+        warn_if_not_head(&args, &repo)?;
+    }
+
+    // The tag type is checked once instead of failing every package.
+    if !args.as_branch && !packages.is_empty() {
+        let force_sign = repo.config()?.get_bool("tag.forceSignAnnotated");
+
+        if let (TagType::Annotated, Ok(true)) = (tag_type(&args, &repo)?, force_sign) {
+            args.print("warning: tag.forceSignAnnotated is set, but tags are not signed");
+        }
+    }
+
+    let started = Instant::now();
+
+    let remote_refs = if args.check_remote && !packages.is_empty() {
+        remote_refs(&args, &repo)?
+    } else {
+        HashSet::new()
+    };
+
+    let mut created = Vec::new();
+    let mut failed_pushes = 0;
+    let per_tag = args.push
+        && args.push_mode == PushMode::PerTag
+        && !args.atomic
+        && args.combined_tag.is_none();
+
+    if let Some(tag_name) = &args.combined_tag {
+        if args.atomic && !prunable {
+            for package in &packages {
+                results.push(
+                    PackageResult::new(&args, package, Some(tag_name), Action::Skipped)
+                        .with_reason(SkipReason::Aborted),
+                );
+            }
+        } else if !packages.is_empty() {
+            let (action, outcome) =
+                match create_combined_tag(&args, &packages, &repo, tag_name, &remote_refs) {
+                    Ok(Release::Created(refname)) => {
+                        created.push(refname);
+                        (Action::Created, None)
+                    }
+                    Ok(Release::Skipped(reason)) => (Action::Skipped, Some(Ok(reason))),
+                    Err(err) => {
+                        args.print_progress(format!("failed to create {tag_name:?}: {err}"));
+                        (Action::Failed, Some(Err(err)))
+                    }
+                };
+
+            for package in &packages {
+                let result = PackageResult::new(&args, package, Some(tag_name), action);
+
+                results.push(match &outcome {
+                    Some(Ok(reason)) => result.with_reason(*reason),
+                    Some(Err(err)) => result.with_error(err),
+                    None => result,
+                });
+            }
+        }
+    } else {
+        // Nothing is tagged atomically if any manifest could not be read.
+        let mut aborted = args.atomic && !prunable;
+        let mut previous = Vec::new();
+
+        for package in &packages {
+            if aborted {
+                results.push(
+                    PackageResult::new(&args, package, None, Action::Skipped)
+                        .with_reason(SkipReason::Aborted),
+                );
+                continue;
+            }
+
+            let previous_target = repo
+                .refname_to_id(&release_refname(&args, &package.tag_name(&args)))
+                .ok();
+
+            match create_tag(&args, package, &repo, &remote_refs) {
+                Ok(Release::Created(refname)) => {
+                    let mut result = PackageResult::new(&args, package, None, Action::Created);
+
+                    if per_tag {
+                        let pushed = Instant::now();
+
+                        if let Err(err) = push(&args, &repo, std::slice::from_ref(&refname)) {
+                            result = PackageResult {
+                                action: Action::Failed,
+                                ..result
+                            }
+                            .with_error(&err);
+                            failed_pushes += 1;
+                        }
+
+                        timings.push += pushed.elapsed().as_secs_f64();
+                    }
+
+                    previous.push((refname.clone(), previous_target));
+                    created.push(refname);
+                    results.push(result);
+                }
+                Ok(Release::Skipped(reason)) => {
+                    results.push(
+                        PackageResult::new(&args, package, None, Action::Skipped)
+                            .with_reason(reason),
+                    );
+                }
+                Err(err) => {
+                    args.print_progress(format!(
+                        "failed to process {} ({}, tag {}): {}",
+                        log_path(&package.manifest),
+                        package.ecosystem.name(),
+                        package.tag_name(&args),
+                        err
+                    ));
+                    results.push(
+                        PackageResult::new(&args, package, None, Action::Failed).with_error(&err),
+                    );
+                    aborted = args.atomic;
+                }
+            }
+        }
+
+        if aborted {
+            rollback(&args, &repo, &previous)?;
+            created.clear();
+
+            for result in &mut results {
+                if result.action == Action::Created {
+                    result.action = Action::Skipped;
+                    result.reason = Some(SkipReason::Aborted);
+                }
+            }
+        }
+    }
+
+    let finished = if created.is_empty() {
+        Ok(())
+    } else {
+        add_note(&args, &repo)
+    };
+
+    // Tags pushed one by one are not part of the time spent tagging.
+    timings.tagging = started.elapsed().as_secs_f64() - timings.push;
+    let started = Instant::now();
+
+    let finished = finished.and_then(|_| {
+        if failed_pushes > 0 {
+            Err(NetworkError(format!("failed to push {failed_pushes} tag(s)")).into())
+        } else if args.push && !per_tag && !created.is_empty() {
+            push(&args, &repo, &created)
+        } else {
+            Ok(())
+        }
+    });
+
+    timings.push += started.elapsed().as_secs_f64();
+
+    let finished = finished.and_then(|_| {
+        if !args.prune {
+            Ok(())
+        } else if !prunable {
+            Err(anyhow!(
+                "refusing to prune, some manifests could not be read"
+            ))
+        } else {
+            prune(&args, &repo, &packages, &disabled)
+        }
+    });
+
+    print_report(&args, &results, args.timings.then_some(&timings))?;
+
+    if let Some(path) = &args.summary_markdown {
+        write_markdown_summary(&args, &results, path)
+            .map_err(|err| err.context(format!("failed to write {:?}", path)))?;
+    }
+
+    if let Some(path) = &args.sbom_out {
+        write_sbom(&args, &repo, &results, path)
+            .map_err(|err| err.context(format!("failed to write {:?}", path)))?;
+    }
+
+    if let Some(url) = &args.notify_webhook {
+        let summary = Summary::new(&results);
+
+        let notify = match args.notify_on {
+            NotifyOn::Created => summary.created > 0,
+            NotifyOn::Always => true,
+            NotifyOn::Failure => finished.is_err() || summary.failed > 0,
+        };
+
+        if notify {
+            let report = Report {
+                dry_run: args.dry_run,
+                packages: &results,
+                summary,
+                timings: args.timings.then_some(&timings),
+            };
+
+            if let Err(err) = notify_webhook(&args, &repo, url, report) {
+                args.print(format!("warning: failed to notify {url:?}: {err}"));
+            }
+        }
+    }
+
+    finished?;
+
+    let summary = Summary::new(&results);
+
+    if summary.failed > 0 {
+        return Err(PackagesFailed(summary.failed).into());
+    }
+
+    if args.exit_code && summary.created > 0 {
+        std::process::exit(error::EXIT_FAILED);
+    }
+
+    Ok(())
+}
+
+/// Makes sure that the external program an option relies on is installed.
+fn require_program(program: &str, option: &str) -> Result<(), anyhow::Error> {
+    let status = std::process::Command::new(program)
+        .arg("--version")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+
+    match status {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(UsageError(format!(
+            "{option} requires {program}, which was not found in PATH"
+        ))
+        .into()),
+        Err(err) => Err(UsageError(format!(
+            "{option} requires {program}, which failed to run: {err}"
+        ))
+        .into()),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// The results posted to `--notify-webhook`.
+#[derive(serde::Serialize)]
+struct Notification<'a> {
+    /// The URL of the first remote given via `--remote`.
+    repository: Option<String>,
+    commit: String,
+    #[serde(flatten)]
+    report: Report<'a>,
+}
+
+/// POSTs the report along with the repository and commit to the webhook.
+fn notify_webhook(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    url: &str,
+    report: Report,
+) -> Result<(), anyhow::Error> {
+    let notification = Notification {
+        repository: args
+            .remote
+            .first()
+            .and_then(|remote| repo.find_remote(remote).ok())
+            .and_then(|remote| remote.url().map(str::to_string)),
+        commit: target_commit(args, repo)?.id().to_string(),
+        report,
+    };
+
+    if args.dry_run {
+        args.print_progress(format!("would notify {url:?}"));
+        return Ok(());
+    }
+
+    let mut command = std::process::Command::new("curl");
+    command.args(["--silent", "--show-error", "--fail", "--request", "POST"]);
+    command.args([
+        "--header",
+        "Content-Type: application/json",
+        "--data-binary",
+        "@-",
+    ]);
+
+    if args.network_timeout > 0 {
+        command.args(["--max-time", &args.network_timeout.to_string()]);
+    }
+
+    if let Some(proxy) = &args.proxy {
+        command.args(["--proxy", proxy]);
+    }
+
+    let mut child = command
+        .arg(url)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("failed to run curl: {err}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(serde_json::to_string(&notification)?.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    args.print_progress(format!("notified {url:?}"));
+
+    Ok(())
+}
+
+fn print_report(
+    args: &AutoTagArgs,
+    results: &[PackageResult],
+    timings: Option<&Timings>,
+) -> Result<(), anyhow::Error> {
+    let summary = Summary::new(results);
+
+    if args.dry_run && args.dry_run_format == DryRunFormat::Github {
+        print_annotations(args, results);
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            let created = if args.dry_run { "to create" } else { "created" };
+
+            let reasons: Vec<String> = summary
+                .skip_reasons
+                .iter()
+                .map(|(reason, count)| format!("{count} {}", reason.name()))
+                .collect();
+
+            let reasons = if reasons.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", reasons.join(", "))
+            };
+
+            args.print_output(format!(
+                "{} {created}, {} skipped{reasons}, {} failed",
+                summary.created, summary.skipped, summary.failed
+            ))?;
+
+            if let Some(timings) = timings {
+                args.print_output(format!("timings: {}", timings.describe()))?;
+            }
+        }
+        OutputFormat::Csv => {
+            print_csv(args, results, &summary)?;
+
+            if let Some(timings) = timings {
+                args.print_output(format!("# timings: {}", timings.describe()))?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml if args.summary_only => {
+            args.print_data(&summary)?;
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let report = Report {
+                dry_run: args.dry_run,
+                packages: results,
+                summary,
+                timings,
+            };
+
+            args.print_data(&report)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints a GitHub Actions workflow command for every package that would be
+/// tagged or failed, annotating its manifest.
+fn print_annotations(args: &AutoTagArgs, results: &[PackageResult]) {
+    for result in results {
+        let (level, message) = match (result.action, &result.error) {
+            (Action::Created, _) => (
+                "notice",
+                format!(
+                    r#"would create tag "{}""#,
+                    result.tag.as_deref().unwrap_or_default()
+                ),
+            ),
+            (Action::Failed, Some(error)) => ("error", error.clone()),
+            _ => continue,
+        };
+
+        let manifest = result.manifest.to_string_lossy();
+        let file = manifest.strip_prefix("./").unwrap_or(&manifest);
+
+        args.print(format!(
+            "::{level} file={}::{}",
+            workflow_escape(file, true),
+            workflow_escape(&message, false)
+        ));
+    }
+}
+
+/// Escapes a value for use in a GitHub Actions workflow command.
+fn workflow_escape(value: &str, property: bool) -> String {
+    let value = value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+
+    if property {
+        value.replace(':', "%3A").replace(',', "%2C")
+    } else {
+        value
+    }
+}
+
+/// Prints a row for every package, followed by the summary as a comment.
+fn print_csv(
+    args: &AutoTagArgs,
+    results: &[PackageResult],
+    summary: &Summary,
+) -> Result<(), anyhow::Error> {
+    if !args.summary_only {
+        args.print_output("ecosystem,manifest,name,version,tag,action,reason")?;
+
+        for result in results {
+            let fields = [
+                result.ecosystem.name(),
+                &result.manifest.to_string_lossy(),
+                result.name.as_deref().unwrap_or_default(),
+                result.version.as_deref().unwrap_or_default(),
+                result.tag.as_deref().unwrap_or_default(),
+                result.action.name(),
+                result
+                    .error
+                    .as_deref()
+                    .or_else(|| result.reason.map(SkipReason::name))
+                    .unwrap_or_default(),
+            ];
+
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            args.print_output(row.join(","))?;
+        }
+    }
+
+    args.print_output(format!(
+        "# {} created, {} skipped, {} failed",
+        summary.created, summary.skipped, summary.failed
+    ))
+}
+
+/// A package listed in the `--sbom-out` file.
+#[derive(serde::Serialize)]
+struct SbomPackage<'a> {
+    ecosystem: Ecosystem,
+    name: &'a str,
+    version: &'a str,
+    tag: &'a str,
+    commit: &'a str,
+    /// Only listed with `--sbom-all`, as all other packages were tagged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<Action>,
+}
+
+#[derive(serde::Serialize)]
+struct Sbom<'a> {
+    packages: Vec<SbomPackage<'a>>,
+}
+
+/// Writes the tagged packages, or all packages with `--sbom-all`, to the
+/// file as JSON.
+fn write_sbom(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    results: &[PackageResult],
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    let commit = target_commit(args, repo)?.id().to_string();
+
+    let packages: Vec<SbomPackage> = results
+        .iter()
+        .filter(|result| args.sbom_all || result.action == Action::Created)
+        .filter_map(|result| {
+            Some(SbomPackage {
+                ecosystem: result.ecosystem,
+                name: result.name.as_deref()?,
+                version: result.version.as_deref()?,
+                tag: result.tag.as_deref()?,
+                commit: &commit,
+                action: args.sbom_all.then_some(result.action),
+            })
+        })
+        .collect();
+
+    let sbom = serde_json::to_string_pretty(&Sbom { packages })?;
+    std::fs::write(path, sbom + "\n")?;
+
+    Ok(())
+}
+
+/// Writes a table with the results of every package, followed by the
+/// summary, to the file.
+fn write_markdown_summary(
+    args: &AutoTagArgs,
+    results: &[PackageResult],
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    let summary = Summary::new(results);
+    let created = if args.dry_run { "to create" } else { "created" };
+
+    let mut markdown = String::new();
+
+    // Appended tables need a blank line to not continue the previous one.
+    if args.summary_append && std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0) {
+        markdown.push('\n');
+    }
+
+    markdown.push_str("| Package | Version | Tag | Action |\n| --- | --- | --- | --- |\n");
+
+    for result in results {
+        let name = match &result.name {
+            Some(name) => name.clone(),
+            None => result.manifest.to_string_lossy().into_owned(),
+        };
+
+        let action = match (&result.error, result.reason) {
+            (Some(error), _) => format!("{}: {error}", result.action.name()),
+            (None, Some(reason)) => format!("{} ({})", result.action.name(), reason.name()),
+            (None, None) if result.action == Action::Created => created.to_string(),
+            (None, None) => result.action.name().to_string(),
+        };
+
+        let cells = [
+            name.as_str(),
+            result.version.as_deref().unwrap_or_default(),
+            result.tag.as_deref().unwrap_or_default(),
+            &action,
+        ];
+
+        let row: Vec<String> = cells.iter().map(|cell| markdown_cell(cell)).collect();
+        markdown.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+
+    markdown.push_str(&format!(
+        "\n{} {created}, {} skipped, {} failed\n",
+        summary.created, summary.skipped, summary.failed
+    ));
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(args.summary_append)
+        .truncate(!args.summary_append)
+        .open(path)?;
+
+    file.write_all(markdown.as_bytes())?;
+
+    Ok(())
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// Quotes the field if it contains characters with special meaning in CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Makes sure that every ecosystem with manifests has at least one manifest
+/// configuring tagging.
+fn check_configured(
+    packages: &[Package],
+    results: &[PackageResult],
+    unconfigured: &[Ecosystem],
+) -> Result<(), anyhow::Error> {
+    let missing: Vec<&str> = unconfigured
+        .iter()
+        .filter(|&&ecosystem| {
+            !packages
+                .iter()
+                .any(|package| package.ecosystem == ecosystem)
+                && !results.iter().any(|result| {
+                    result.ecosystem == ecosystem && result.reason != Some(SkipReason::Unconfigured)
+                })
+        })
+        .map(|ecosystem| ecosystem.name())
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(UsageError(format!(
+            "none of the manifests configure tagging for {}",
+            missing.join(", ")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Makes sure that no two packages get the same tag.
+fn check_tag_collisions(args: &AutoTagArgs, packages: &[Package]) -> Result<(), anyhow::Error> {
+    let collisions: Vec<String> = tag_collisions(args, packages)
+        .into_iter()
+        .map(|indices| {
+            let manifests: Vec<String> = indices
+                .iter()
+                .map(|&i| packages[i].manifest.display().to_string())
+                .collect();
+
+            format!(
+                r#"  "{}": {}"#,
+                packages[indices[0]].tag_name(args),
+                manifests.join(", ")
+            )
+        })
+        .collect();
+
+    if !collisions.is_empty() {
+        return Err(UsageError(format!(
+            "refusing to create tags, some packages would get the same tag \
+             (see --qualify-with-ecosystem):\n{}",
+            collisions.join("\n")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Returns the indices of packages that would get the same tag, grouped by
+/// tag in the order the packages were found.
+fn tag_collisions(args: &AutoTagArgs, packages: &[Package]) -> Vec<Vec<usize>> {
+    let mut tags: Vec<(String, Vec<usize>)> = Vec::new();
+
+    for (i, package) in packages.iter().enumerate() {
+        let tag_name = package.tag_name(args);
+
+        match tags.iter_mut().find(|(tag, _)| *tag == tag_name) {
+            Some((_, indices)) => indices.push(i),
+            None => tags.push((tag_name, vec![i])),
+        }
+    }
+
+    tags.into_iter()
+        .map(|(_, indices)| indices)
+        .filter(|indices| indices.len() > 1)
+        .collect()
+}
+
+/// Warns about packages with tag names that only differ in case, as these
+/// collide on case-insensitive file systems.
+fn warn_case_collisions(args: &AutoTagArgs, packages: &[Package]) {
+    for collisions in case_collisions(args, packages) {
+        let collisions: Vec<String> = collisions
+            .iter()
+            .map(|(tag, manifest)| format!(r#""{tag}" ({})"#, manifest.display()))
+            .collect();
+
+        args.print(format!(
+            "warning: tag names only differ in case: {}",
+            collisions.join(", ")
+        ));
+    }
+}
+
+/// Groups the tag names of the packages that only differ in case, together
+/// with their manifests.
+fn case_collisions<'p>(
+    args: &AutoTagArgs,
+    packages: &'p [Package],
+) -> Vec<Vec<(String, &'p Path)>> {
+    let mut tags: HashMap<String, Vec<(String, &Path)>> = HashMap::new();
+
+    for package in packages {
+        let tag_name = package.tag_name(args);
+
+        tags.entry(tag_name.to_lowercase())
+            .or_default()
+            .push((tag_name, &package.manifest));
+    }
+
+    tags.into_values()
+        .filter(|collisions| collisions.iter().any(|(tag, _)| *tag != collisions[0].0))
+        .collect()
+}
+
+/// Warns if the commit given via `--commit` is not the one HEAD points to.
+fn warn_if_not_head(args: &AutoTagArgs, repo: &Repository) -> Result<(), anyhow::Error> {
+    let target = target_commit(args, repo)?.id();
+
+    if let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) {
+        if head.id() != target {
+            args.print(format!(
+                r#"warning: tagging commit "{}" which is not HEAD ("{}")"#,
+                short_sha(args, repo, target),
+                short_sha(args, repo, head.id())
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes sure that HEAD is on the given branch.
+fn check_branch(repo: &Repository, branch: &str) -> Result<(), anyhow::Error> {
+    let head = repo.head()?;
+
+    if !head.is_branch() {
+        return Err(anyhow!(
+            r#"HEAD is detached instead of being on branch "{branch}""#
+        ));
+    }
+
+    let current = head.shorthand().unwrap_or_default();
+
+    if current != branch {
+        return Err(anyhow!(
+            r#"HEAD is on branch "{current}" instead of "{branch}""#
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct EcosystemEntry<'a> {
+    ecosystem: &'static str,
+    file_names: Vec<String>,
+    enable_key: &'a str,
+}
+
+/// Prints the supported ecosystems, including the external detector if given.
+fn ecosystems(args: &AutoTagArgs) -> Result<(), anyhow::Error> {
+    let detector = Detector::from_args(args)?;
+
+    let entries: Vec<EcosystemEntry> = SOURCES
+        .iter()
+        .copied()
+        .chain(detector.as_ref().map(|d| d as &dyn PackageSource))
+        .map(|source| EcosystemEntry {
+            ecosystem: source.ecosystem().name(),
+            file_names: source.file_names(),
+            enable_key: sources::enable_key(args, source.ecosystem(), source.enable_key()),
+        })
+        .collect();
+
+    match args.output {
+        OutputFormat::Text => {
+            for entry in &entries {
+                args.print_output(format!(
+                    "{}: {} ({})",
+                    entry.ecosystem,
+                    entry.file_names.join(", "),
+                    entry.enable_key
+                ))?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Csv => args.print_data(&entries)?,
+    }
+
+    Ok(())
+}
+
+/// Returns all files within the given directories of the working directory.
+fn work_dir_files(args: &AutoTagArgs, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    // Directories are only searched once, even if they can be reached
+    // through symlinks or overlapping paths, which also prevents loops.
+    let mut visited = HashSet::new();
+
+    for arg in paths {
+        if arg.is_dir() && !first_visit(args, &mut visited, arg) {
+            continue;
+        }
+
+        // Sorted so that results are in the same order on every run.
+        let walker = WalkDir::new(arg).sort_by_file_name().into_iter();
+
+        // Symlinks below the given paths are not followed, so only actual
+        // directories can be reached twice.
+        for entry in walker.filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || first_visit(args, &mut visited, entry.path())
+        }) {
+            match entry {
+                Ok(entry) => files.push(entry.into_path()),
+                Err(err) => args.print_progress(format!("cannot access file: {}", err)),
+            }
+        }
+    }
+
+    files
+}
+
+/// Records the directory as visited, returns `false` with a warning if it
+/// already was.
+fn first_visit(args: &AutoTagArgs, visited: &mut HashSet<PathBuf>, dir: &Path) -> bool {
+    let canonical = match dir.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => return true,
+    };
+
+    if !visited.insert(canonical) {
+        args.print_progress(format!(
+            "skipping {:?}, the directory was already searched",
+            dir
+        ));
+        return false;
+    }
+
+    true
+}
+
+/// Returns all files within the given directories of the tree.
+///
+/// The directories are relative to the root of the repository.
+fn tree_files(tree: &git2::Tree, paths: &[PathBuf]) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut all_files = Vec::new();
+
+    tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                all_files.push(PathBuf::from(format!("{}{}", root, name)));
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    let dirs: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| {
+            path.components()
+                .filter(|component| *component != std::path::Component::CurDir)
+                .collect()
+        })
+        .collect();
+
+    Ok(all_files
+        .into_iter()
+        .filter(|file| dirs.iter().any(|dir| file.starts_with(dir)))
+        .collect())
+}
+
+/// Returns whether the path contains glob syntax.
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '[', '{'])
+}
+
+/// Replaces glob patterns in the paths with the directories they match,
+/// either in the tree if given or in the working directory.
+///
+/// Directories nested within another match are left out, their files are
+/// already searched.
+fn expand_paths(
+    paths: &[PathBuf],
+    tree: Option<&git2::Tree>,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut expanded = Vec::new();
+
+    for path in paths {
+        if !is_glob(path) {
+            expanded.push(path.clone());
+            continue;
+        }
+
+        let pattern = path.to_string_lossy();
+        let pattern = pattern.strip_prefix("./").unwrap_or(&pattern);
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| UsageError(format!("invalid path {path:?}: {err}")))?
+            .compile_matcher();
+
+        let dirs = match tree {
+            Some(tree) => tree_dirs(tree)?,
+            None => {
+                // Only the part of the path before the first glob needs to
+                // be searched.
+                let base: PathBuf = path
+                    .components()
+                    .take_while(|component| !is_glob(Path::new(component.as_os_str())))
+                    .collect();
+                let base = if base.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    base
+                };
+
+                WalkDir::new(base)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_dir())
+                    .map(|entry| entry.into_path())
+                    .collect()
+            }
+        };
+
+        let mut matched: Vec<PathBuf> = Vec::new();
+
+        for dir in dirs {
+            let relative = dir.strip_prefix(".").unwrap_or(&dir);
+
+            if matcher.is_match(relative) && !matched.iter().any(|other| dir.starts_with(other)) {
+                matched.push(dir);
+            }
+        }
+
+        if matched.is_empty() {
+            return Err(UsageError(format!("{path:?} does not match any directories")).into());
+        }
+
+        expanded.extend(matched);
+    }
+
+    Ok(expanded)
+}
+
+/// Returns the paths given with `--no-walk`, making sure that they exist and
+/// are recognized manifests.
+fn manifest_files(
+    paths: &[PathBuf],
+    tree: Option<&git2::Tree>,
+    sources: &[&dyn PackageSource],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    check_paths(paths, tree)?;
+
+    let unrecognized: Vec<String> = paths
+        .iter()
+        .filter(|path| !sources.iter().any(|source| source.matches(path)))
+        .map(|path| format!("{path:?}"))
+        .collect();
+
+    if !unrecognized.is_empty() {
+        return Err(UsageError(format!(
+            "paths are not recognized manifests: {}",
+            unrecognized.join(", ")
+        ))
+        .into());
+    }
+
+    // Paths in trees are relative to the root of the repository.
+    Ok(paths
+        .iter()
+        .map(|path| match tree {
+            Some(_) => path
+                .components()
+                .filter(|component| *component != std::path::Component::CurDir)
+                .collect(),
+            None => path.clone(),
+        })
+        .collect())
+}
+
+/// Makes sure that all paths exist, either in the tree if given or in the
+/// working directory, listing the ones that do not.
+fn check_paths(paths: &[PathBuf], tree: Option<&git2::Tree>) -> Result<(), anyhow::Error> {
+    let missing: Vec<String> = paths
+        .iter()
+        .filter(|path| match tree {
+            Some(tree) => {
+                let path: PathBuf = path
+                    .components()
+                    .filter(|component| *component != std::path::Component::CurDir)
+                    .collect();
+                !path.as_os_str().is_empty() && tree.get_path(&path).is_err()
+            }
+            None => !path.exists(),
+        })
+        .map(|path| format!("{path:?}"))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(UsageError(format!("paths not found: {}", missing.join(", "))).into());
+    }
+
+    Ok(())
+}
+
+/// Returns all directories of the tree, relative to the root of the
+/// repository.
+fn tree_dirs(tree: &git2::Tree) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut dirs = Vec::new();
+
+    tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(git2::ObjectType::Tree) {
+            if let Some(name) = entry.name() {
+                dirs.push(PathBuf::from(format!("{}{}", root, name)));
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    Ok(dirs)
+}
+
+/// Returns the manifests of the members of the Cargo workspace at the root,
+/// following `members` or `default-members` and `exclude` like Cargo does.
+///
+/// The manifest of the root is included if it is also a package.
+fn workspace_members(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    tree: Option<&git2::Tree>,
+    root: &Path,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let root_dir = match root.parent() {
+        Some(parent) if root.ends_with("Cargo.toml") && !parent.as_os_str().is_empty() => parent,
+        _ if root.ends_with("Cargo.toml") => Path::new("."),
+        _ => root,
+    };
+    // Paths in trees have no `.` components.
+    let root_dir: PathBuf = match tree {
+        Some(_) => root_dir
+            .components()
+            .filter(|component| *component != std::path::Component::CurDir)
+            .collect(),
+        None => root_dir.to_path_buf(),
+    };
+    let root_manifest = root_dir.join("Cargo.toml");
+
+    let contents = read_manifest(repo, tree, &root_manifest)?;
+    let cargo_toml: toml::Value = toml::from_str(&contents)?;
+
+    let workspace = cargo_toml
+        .get("workspace")
+        .ok_or_else(|| UsageError(format!("{:?} is not a Cargo workspace root", root_manifest)))?;
+
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|value| value.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str())
+            .map(|value| {
+                value
+                    .trim_start_matches("./")
+                    .trim_end_matches('/')
+                    .to_string()
+            })
+            .collect()
+    };
+
+    let members = match strings("default-members") {
+        default_members if !default_members.is_empty() => default_members,
+        _ => strings("members"),
+    };
+    let excluded = strings("exclude");
+
+    let mut patterns = Vec::new();
+
+    for member in &members {
+        let pattern = GlobBuilder::new(member)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| UsageError(format!("invalid workspace member {member:?}: {err}")))?
+            .compile_matcher();
+        patterns.push(pattern);
+    }
+
+    let files = match tree {
+        Some(tree) => tree_files(tree, std::slice::from_ref(&root_dir))?,
+        None => work_dir_files(args, std::slice::from_ref(&root_dir)),
+    };
+
+    let mut manifests = Vec::new();
+
+    if cargo_toml.get("package").is_some() || args.cargo_workspace_tag {
+        manifests.push(root_manifest.clone());
+    }
+
+    for file in files {
+        if !file.ends_with("Cargo.toml") || file == root_manifest {
+            continue;
+        }
+
+        let dir = match file
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&root_dir).ok())
+        {
+            Some(dir) => dir,
+            None => continue,
+        };
+
+        let included = patterns.iter().any(|pattern| pattern.is_match(dir))
+            && !excluded.iter().any(|excluded| dir.starts_with(excluded));
+
+        if included {
+            manifests.push(file);
+        }
+    }
+
+    Ok(manifests)
+}
+
+/// Returns the manifests of the members of the uv workspaces whose root
+/// `pyproject.toml` is given directly in the paths.
+///
+/// Members of workspaces given as directories are found by searching them.
+fn uv_workspace_members(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    tree: Option<&git2::Tree>,
+    paths: &[PathBuf],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut manifests = Vec::new();
+
+    for root in paths {
+        if !root.ends_with("pyproject.toml") {
+            continue;
+        }
+
+        // Paths in trees have no `.` components.
+        let root: PathBuf = match tree {
+            Some(_) => root
+                .components()
+                .filter(|component| *component != std::path::Component::CurDir)
+                .collect(),
+            None => root.clone(),
+        };
+
+        // Unreadable manifests are reported when their package is read.
+        let pyproject_toml: toml::Value = match read_manifest(repo, tree, &root)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+        {
+            Some(pyproject_toml) => pyproject_toml,
+            None => continue,
+        };
+
+        let root_dir = match root.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ if tree.is_some() => PathBuf::new(),
+            _ => PathBuf::from("."),
+        };
+
+        let files = match tree {
+            Some(tree) => tree_files(tree, std::slice::from_ref(&root_dir))?,
+            None => work_dir_files(args, std::slice::from_ref(&root_dir)),
+        };
+
+        for file in files {
+            if !file.ends_with("pyproject.toml") || file == root {
+                continue;
+            }
+
+            let is_member = file
+                .parent()
+                .and_then(|dir| dir.strip_prefix(&root_dir).ok())
+                .is_some_and(|dir| sources::is_uv_member(&pyproject_toml, dir));
+
+            if is_member {
+                manifests.push(file);
+            }
+        }
+    }
+
+    Ok(manifests)
+}
+
+/// Returns the path of the manifest as printed in logs, absolute if it is in
+/// the working directory.
+///
+/// Paths of manifests that were read from a tree are relative to the root of
+/// the repository.
+pub(crate) fn log_path(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// Reads the manifest from the tree if given, otherwise from the working
+/// directory.
+///
+/// A leading byte order mark is removed.
+fn read_manifest(
+    repo: &Repository,
+    tree: Option<&git2::Tree>,
+    path: &Path,
+) -> Result<String, AutoTagError> {
+    let contents = match tree {
+        Some(tree) => {
+            let blob = tree.get_path(path)?.to_object(repo)?.peel_to_blob()?;
+            String::from_utf8(blob.content().to_vec())?
+        }
+        None => std::fs::read_to_string(path)?,
+    };
+
+    Ok(sources::strip_bom(&contents).to_string())
+}
+
+/// Returns whether manifests are read from a tree rather than the working
+/// directory.
+fn in_tree(args: &AutoTagArgs, repo: &Repository) -> bool {
+    args.from_tree.is_some() || args.staged || args.merge_base.is_some() || repo.is_bare()
+}
+
+/// Searches the given directories for packages that have tagging enabled.
+fn find_packages(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    paths: &[PathBuf],
+) -> Result<Discovery, anyhow::Error> {
+    let mut packages = Vec::new();
+    let mut disabled = Vec::new();
+    let mut results = Vec::new();
+    let mut unconfigured = Vec::new();
+
+    let tree = match &args.from_tree {
+        Some(revspec) => Some(repo.revparse_single(revspec)?.peel_to_tree()?),
+        // The staged manifests are read from the tree the index would be
+        // committed as.
+        None if args.staged => {
+            let tree = repo.index()?.write_tree().map_err(|err| {
+                anyhow::Error::new(err).context("failed to read the staged manifests")
+            })?;
+            Some(repo.find_tree(tree)?)
+        }
+        None if args.merge_base.is_some() => Some(target_commit(args, repo)?.tree()?),
+        // Bare repositories have no working directory, so manifests are read
+        // from the commit being tagged instead.
+        None if repo.is_bare() => {
+            let commit = target_commit(args, repo).map_err(|err| {
+                anyhow!(
+                    "cannot find a commit to read manifests from in the bare repository, \
+                     use --commit or --from-tree: {}",
+                    err
+                )
+            })?;
+            Some(commit.tree()?)
+        }
+        None => None,
+    };
+
+    let detector = Detector::from_args(args)?;
+
+    // The detector comes first so that it can also handle files that are
+    // otherwise recognized.
+    let sources: Vec<&dyn PackageSource> = detector
+        .as_ref()
+        .map(|detector| detector as &dyn PackageSource)
+        .into_iter()
+        .chain(SOURCES.iter().copied())
+        .collect();
+
+    let started = Instant::now();
+
+    let files = match &args.cargo_workspace {
+        Some(root) => workspace_members(args, repo, tree.as_ref(), root)?,
+        None if args.no_walk => manifest_files(paths, tree.as_ref(), &sources)?,
+        None => {
+            let paths = expand_paths(paths, tree.as_ref())?;
+            check_paths(&paths, tree.as_ref())?;
+
+            let mut files = match &tree {
+                Some(tree) => tree_files(tree, &paths)?,
+                None => work_dir_files(args, &paths),
+            };
+
+            for member in uv_workspace_members(args, repo, tree.as_ref(), &paths)? {
+                if !files.contains(&member) {
+                    files.push(member);
+                }
+            }
+
+            files
+        }
+    };
+
+    let mut timings = Timings {
+        discovery: started.elapsed().as_secs_f64(),
+        ..Timings::default()
+    };
+    let started = Instant::now();
+
+    for path in files {
+        let source = match sources.iter().find(|source| source.matches(&path)) {
+            Some(source) => source,
+            None => continue,
+        };
+
+        let read = |path: &Path| read_manifest(repo, tree.as_ref(), path);
+        let extracted = read(&path)
+            .and_then(|contents| source.extract(args, &path, &contents, &read))
+            .and_then(|extracted| sources::apply_release_file(args, &path, &read, extracted));
+
+        let extracted = match extracted {
+            Ok(Extracted::ScmVersion(mut package)) => match scm_version(args, repo, &package) {
+                Ok(Some(version)) => {
+                    package.version = version;
+                    Ok(Extracted::Package(package))
+                }
+                Ok(None) => Ok(Extracted::Skipped(SkipReason::NoReleaseTags)),
+                Err(err) => Err(err),
+            },
+            extracted => extracted.map_err(anyhow::Error::from),
+        };
+
+        if args.prune
+            && matches!(
+                extracted,
+                Ok(Extracted::Skipped(
+                    SkipReason::Disabled | SkipReason::Unconfigured
+                ))
+            )
+        {
+            if let Some(package) = disabled_package(args, *source, &path, &read) {
+                disabled.push(package);
+            }
+        }
+
+        match extracted {
+            Ok(Extracted::Package(package)) => packages.push(package),
+            Ok(Extracted::ScmVersion(_)) => unreachable!("scm versions are resolved above"),
+            // Most manifests without the key are not meant to be tagged at all,
+            // such as the ones of dependencies, so they are only reported with
+            // `--warn-unconfigured`.
+            Ok(Extracted::Skipped(SkipReason::Unconfigured)) => {
+                if !unconfigured.contains(&source.ecosystem()) {
+                    unconfigured.push(source.ecosystem());
+                }
+
+                if args.warn_unconfigured {
+                    let key = sources::enable_key(args, source.ecosystem(), source.enable_key());
+                    args.print(format!(
+                        "warning: {} does not configure tagging, {key} not found",
+                        log_path(&path)
+                    ));
+                    results.push(PackageResult::skipped(
+                        source.ecosystem(),
+                        &path,
+                        SkipReason::Unconfigured,
+                    ));
+                }
+            }
+            Ok(Extracted::Skipped(reason)) => {
+                args.print_progress(format!(
+                    "skipping {} ({}), {}",
+                    log_path(&path),
+                    source.ecosystem().name(),
+                    reason.describe()
+                ));
+                results.push(PackageResult::skipped(source.ecosystem(), &path, reason));
+            }
+            Err(err) => {
+                args.print_progress(format!(
+                    "failed to process {} ({}): {}",
+                    log_path(&path),
+                    source.ecosystem().name(),
+                    err
+                ));
+                results.push(PackageResult::failed(source.ecosystem(), &path, &err));
+            }
+        }
+    }
+
+    apply_version_overrides(args, &mut packages)?;
+
+    if args.qualify_with_path {
+        qualify_with_path(args, repo, &mut packages)?;
+        qualify_with_path(args, repo, &mut disabled)?;
+    }
+
+    resolve_colocated(args, &mut packages);
+
+    for package in &packages {
+        package.check_tag_name(args)?;
+    }
+
+    timings.parsing = started.elapsed().as_secs_f64();
+
+    Ok(Discovery {
+        packages,
+        disabled,
+        results,
+        unconfigured,
+        timings,
+    })
+}
+
+/// Reads the package of a manifest that does not enable tagging, as if it
+/// did, or returns `None` if it cannot be read that way either.
+fn disabled_package(
+    args: &AutoTagArgs,
+    source: &dyn PackageSource,
+    path: &Path,
+    read: &ReadFile,
+) -> Option<Package> {
+    args.enable_all.set(true);
+
+    let extracted = read(path)
+        .and_then(|contents| source.extract(args, path, &contents, read))
+        .and_then(|extracted| sources::apply_release_file(args, path, read, extracted));
+
+    args.enable_all.set(false);
+
+    match extracted {
+        Ok(Extracted::Package(package)) | Ok(Extracted::ScmVersion(package)) => Some(package),
+        _ => None,
+    }
+}
+
+/// Prefixes the names of packages with the path of their directory, or its
+/// last `--path-qualify-depth` components.
+///
+/// Packages at the root of the repository are left as they are.
+fn qualify_with_path(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    packages: &mut [Package],
+) -> Result<(), anyhow::Error> {
+    for package in packages {
+        let dir = match history::package_dir(repo, &package.manifest, in_tree(args, repo))? {
+            Some(dir) => dir,
+            None => continue,
+        };
+
+        let path = qualified_path(&dir, args.path_qualify_depth);
+
+        if !path.is_empty() {
+            package.name = format!("{path}/{}", package.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the last `depth` components of the directory as they are used in
+/// tag names, separated by `/` regardless of the platform.
+fn qualified_path(dir: &str, depth: Option<usize>) -> String {
+    let components: Vec<String> = dir.split(['/', '\\']).map(ref_segment).collect();
+    let depth = depth.unwrap_or(components.len());
+
+    components[components.len().saturating_sub(depth)..].join("/")
+}
+
+/// Replaces the characters that cannot be used in a component of a
+/// reference name with `-`.
+fn ref_segment(component: &str) -> String {
+    let segment: String = component
+        .chars()
+        .map(|c| match c {
+            ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\' | '@' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+
+    let segment = segment.replace("..", "-");
+    let segment = segment.trim_start_matches('.');
+
+    match segment.strip_suffix(".lock") {
+        Some(segment) => format!("{segment}-lock"),
+        None => segment.to_string(),
+    }
+}
+
+/// Returns the ecosystems of the packages in each directory.
+fn ecosystems_by_dir(packages: &[Package]) -> HashMap<&Path, Vec<Ecosystem>> {
+    let mut dirs: HashMap<&Path, Vec<Ecosystem>> = HashMap::new();
+
+    for package in packages {
+        let ecosystems = dirs.entry(package_dir(package)).or_default();
+
+        if !ecosystems.contains(&package.ecosystem) {
+            ecosystems.push(package.ecosystem);
+        }
+    }
+
+    dirs
+}
+
+fn package_dir(package: &Package) -> &Path {
+    package.manifest.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// Handles directories containing packages of several ecosystems according to
+/// `--primary-ecosystem` and `--qualify-with-ecosystem`.
+fn resolve_colocated(args: &AutoTagArgs, packages: &mut Vec<Package>) {
+    if let Some(primary) = args.primary_ecosystem {
+        let dirs = ecosystems_by_dir(packages);
+
+        let keep: Vec<bool> = packages
+            .iter()
+            .map(|package| {
+                let ecosystems = &dirs[package_dir(package)];
+                ecosystems.len() == 1
+                    || !ecosystems.contains(&primary)
+                    || package.ecosystem == primary
+            })
+            .collect();
+
+        let mut keep = keep.into_iter();
+        packages.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    if args.qualify_with_ecosystem {
+        let mut qualify: Vec<bool> = {
+            let dirs = ecosystems_by_dir(packages);
+            packages
+                .iter()
+                .map(|package| dirs[package_dir(package)].len() > 1)
+                .collect()
+        };
+
+        for indices in tag_collisions(args, packages) {
+            let mut ecosystems: Vec<Ecosystem> =
+                indices.iter().map(|&i| packages[i].ecosystem).collect();
+            ecosystems.sort_by_key(|ecosystem| ecosystem.name());
+            ecosystems.dedup();
+
+            if ecosystems.len() > 1 {
+                for i in indices {
+                    qualify[i] = true;
+                }
+            }
+        }
+
+        for (package, qualify) in packages.iter_mut().zip(qualify) {
+            if qualify {
+                package.name = format!("{}-{}", package.ecosystem.name(), package.name);
+            }
+        }
+    }
+}
+
+/// Applies the versions given via `--set-version`.
+///
+/// Fails if any of the named packages were not found.
+fn apply_version_overrides(
+    args: &AutoTagArgs,
+    packages: &mut [Package],
+) -> Result<(), anyhow::Error> {
+    for package in packages.iter_mut() {
+        if let Some((_, version)) = args
+            .set_version
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == package.package_name)
+        {
+            package.version = version.clone();
+        }
+    }
+
+    let missing: Vec<&str> = args
+        .set_version
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| !packages.iter().any(|package| package.package_name == *name))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(UsageError(format!(
+            "packages given in --set-version were not found: {}",
+            missing.join(", ")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn parse_set_version(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, version) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected <package>=<version>, got {:?}", s))?;
+
+    if name.is_empty() || version.is_empty() {
+        return Err(anyhow!("expected <package>=<version>, got {:?}", s));
+    }
+
+    Ok((name.to_string(), version.to_string()))
+}
+
+fn parse_scope_map(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (scope, package) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected <scope>=<package>, got {:?}", s))?;
+
+    if scope.is_empty() || package.is_empty() {
+        return Err(anyhow!("expected <scope>=<package>, got {:?}", s));
+    }
+
+    Ok((scope.to_string(), package.to_string()))
+}
+
+/// Returns the package name that commits with each conventional commit scope
+/// are attributed to, either given with `--scope-map` or the name of the
+/// package itself.
+fn scope_packages(
+    args: &AutoTagArgs,
+    packages: &[Package],
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let mut scopes = HashMap::new();
+
+    for package in packages {
+        scopes.insert(package.name.clone(), package.package_name.clone());
+        scopes.insert(package.package_name.clone(), package.package_name.clone());
+    }
+
+    let missing: Vec<&str> = args
+        .scope_map
+        .iter()
+        .map(|(_, name)| name.as_str())
+        .filter(|name| !packages.iter().any(|package| package.package_name == *name))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(UsageError(format!(
+            "packages given in --scope-map were not found: {}",
+            missing.join(", ")
+        ))
+        .into());
+    }
+
+    for (scope, name) in &args.scope_map {
+        scopes.insert(scope.clone(), name.clone());
+    }
+
+    Ok(scopes)
+}
+
+fn parse_ecosystem_value(s: &str) -> Result<(Option<Ecosystem>, String), anyhow::Error> {
+    if let Some((ecosystem, value)) = s.split_once('=') {
+        if let Ok(ecosystem) = ecosystem.parse() {
+            return Ok((Some(ecosystem), value.to_string()));
+        }
+    }
+
+    Ok((None, s.to_string()))
+}
+
+fn parse_abbrev(s: &str) -> Result<usize, anyhow::Error> {
+    let abbrev: usize = s.parse()?;
+
+    if !(4..=40).contains(&abbrev) {
+        return Err(anyhow!("must be between 4 and 40"));
+    }
+
+    Ok(abbrev)
+}
+
+/// Returns the commit hash abbreviated as given via `--abbrev`.
+fn short_sha(args: &AutoTagArgs, repo: &Repository, oid: Oid) -> String {
+    let sha = oid.to_string();
+
+    match args.abbrev {
+        Some(abbrev) => sha[..abbrev].to_string(),
+        None => repo
+            .find_object(oid, None)
+            .and_then(|object| object.short_id())
+            .ok()
+            .and_then(|short| short.as_str().map(str::to_string))
+            .unwrap_or(sha),
+    }
+}
+
+/// Replaces `${VAR}` with the value of the environment variable, and
+/// `{key}` with the given value for the key.
+///
+/// The template is scanned once, so placeholders within the substituted
+/// values are kept as they are. Variables that are not set are replaced with
+/// nothing, and returned alongside the result.
+fn render_template(template: &str, values: &[(&str, &str)]) -> (String, Vec<String>) {
+    let mut result = String::new();
+    let mut unset = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let key = &rest[start + 1..end];
+
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+
+            match std::env::var(key) {
+                Ok(value) => result.push_str(&value),
+                Err(_) => unset.push(key.to_string()),
+            }
+        } else if let Some((_, value)) = values.iter().find(|(name, _)| *name == key) {
+            result.push_str(&rest[..start]);
+            result.push_str(value);
+        } else {
+            // Not a placeholder, but a later `{` can still start one.
+            result.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+
+    (result, unset)
+}
+
+/// Makes sure that the value can be used within a tag name.
+fn parse_ref_fragment(s: &str) -> Result<String, anyhow::Error> {
+    if !git2::Reference::is_valid_name(&format!("refs/tags/a{s}b")) {
+        return Err(anyhow!("{:?} cannot be used in a tag name", s));
+    }
+
+    Ok(s.to_string())
+}
+
+/// Returns the last value given for the ecosystem, falling back to the
+/// last value given without an ecosystem.
+fn ecosystem_value(values: &[(Option<Ecosystem>, String)], ecosystem: Ecosystem) -> Option<&str> {
+    values
+        .iter()
+        .rev()
+        .find(|(e, _)| *e == Some(ecosystem))
+        .or_else(|| values.iter().rev().find(|(e, _)| e.is_none()))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Returns the name and email of the tagger, falling back to the committer of
+/// the commit with `--tagger-from-commit`.
+fn git_user(args: &AutoTagArgs, commit: &Commit) -> Result<(String, String), anyhow::Error> {
+    let committer = commit.committer();
+
+    let (commit_user, commit_email) = if args.tagger_from_commit {
+        (committer.name(), committer.email())
+    } else {
+        (None, None)
+    };
+
+    let git_user = args
+        .git_user_name
+        .as_deref()
+        .or(commit_user)
+        .ok_or_else(|| anyhow!("git user name is required"))?;
+    let git_email = args
+        .git_user_email
+        .as_deref()
+        .or(commit_email)
+        .ok_or_else(|| anyhow!("git user email is required"))?;
+
+    Ok((git_user.to_string(), git_email.to_string()))
+}
+
+/// Returns the signature of the tagger, at the time of the commit with
+/// `--tagger-from-commit`.
+fn tagger(args: &AutoTagArgs, commit: &Commit) -> Result<Signature<'static>, anyhow::Error> {
+    let (git_user, git_email) = git_user(args, commit)?;
+
+    let signature = if args.tagger_from_commit {
+        Signature::new(&git_user, &git_email, &commit.committer().when())?
+    } else {
+        Signature::now(&git_user, &git_email)?
+    };
+
+    Ok(signature)
+}
+
+fn target_commit<'r>(
+    args: &AutoTagArgs,
+    repo: &'r Repository,
+) -> Result<Commit<'r>, anyhow::Error> {
+    let commit = if let Some(sha) = &args.commit {
+        repo.find_commit(Oid::from_str(sha)?)?
+    } else if let Some(base) = &args.merge_base {
+        let head = repo.head()?.peel_to_commit()?.id();
+        let base_commit = repo.revparse_single(base)?.peel_to_commit()?.id();
+        let merge_base = repo.merge_base(head, base_commit).map_err(|err| {
+            anyhow::Error::new(err).context(format!(
+                "failed to find the merge base of HEAD and {base:?}"
+            ))
+        })?;
+        repo.find_commit(merge_base)?
+    } else {
+        repo.head()?.peel_to_commit()?
+    };
+
+    Ok(commit)
+}
+
+/// Creates the release tag for the package.
+///
+/// Returns the full name of the created reference, if one was created
+/// (or would be in dry-run mode).
+fn create_tag(
+    args: &AutoTagArgs,
+    package: &Package,
+    repo: &Repository,
+    remote_refs: &HashSet<String>,
+) -> Result<Release, anyhow::Error> {
+    if let Some(existing) = equivalent_release(args, repo, package)? {
+        let kind = if args.as_branch { "branch" } else { "tag" };
+        args.print_existing(format!(
+            r#"{kind} "{existing}" already exists for version {}, skipping..."#,
+            package.version
+        ));
+        return Ok(Release::Skipped(SkipReason::AlreadyExists));
+    }
+
+    if args.idempotent {
+        if let Some(existing) = released_with_other_tag(args, repo, package)? {
+            args.print_progress(format!(
+                r#"tag "{existing}" already releases version {} of the commit, skipping..."#,
+                package.version
+            ));
+            return Ok(Release::Skipped(SkipReason::AlreadyReleased));
+        }
+    }
+
+    if args.since_last_tag {
+        if let Some((tag, since)) = latest_release_commit(args, repo, package)? {
+            let dir = history::package_dir(repo, &package.manifest, in_tree(args, repo))?;
+            let target = target_commit(args, repo)?.id();
+
+            if history::commits_touching(
+                repo,
+                target,
+                Some(since),
+                args.first_parent,
+                dir.as_deref(),
+            )?
+            .is_empty()
+            {
+                args.print_progress(format!(
+                    r#"{} has not changed since "{tag}", skipping..."#,
+                    package.name
+                ));
+                return Ok(Release::Skipped(SkipReason::UnchangedSinceLastRelease));
+            }
+        }
+    }
+
+    if args.version_changed_only && !version_changed(args, repo, package)? {
+        args.print_progress(format!(
+            "the version of {} did not change, skipping...",
+            package.name
+        ));
+        return Ok(Release::Skipped(SkipReason::VersionUnchanged));
+    }
+
+    let Package { name, version, .. } = package;
+    let mut tag_message = match &args.message_file {
+        Some(path) => read_message_file(path)?,
+        None => format!("automatic release tag of {} ({})", name, version),
+    };
+
+    if args.notes_from_log {
+        let notes = release_notes(args, repo, package)?;
+
+        if !notes.is_empty() {
+            tag_message.push_str("\n\n");
+            tag_message.push_str(&notes);
+        }
+    }
+
+    create_release_ref(
+        args,
+        repo,
+        &package.tag_name(args),
+        &tag_message,
+        remote_refs,
+    )
+}
+
+/// Reads the tag message given via `--message-file`.
+fn read_message_file(path: &Path) -> Result<String, anyhow::Error> {
+    std::fs::read_to_string(path)
+        .map_err(|err| anyhow::Error::new(err).context(format!("failed to read {:?}", path)))
+}
+
+/// Returns the name of an annotated tag of the target commit with a different
+/// name, whose message says it releases the version of the package.
+fn released_with_other_tag(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<Option<String>, anyhow::Error> {
+    let commit = target_commit(args, repo)?.id();
+    let tag_name = package.tag_name(args);
+
+    let messages = [&package.name, &package.package_name]
+        .map(|name| format!("automatic release tag of {} ({})", name, package.version));
+
+    for name in repo.tag_names(None)?.iter().flatten() {
+        if name == tag_name {
+            continue;
+        }
+
+        let tag = match repo
+            .revparse_single(&format!("refs/tags/{name}"))
+            .and_then(|object| object.peel_to_tag())
+        {
+            Ok(tag) => tag,
+            // Lightweight tags have no message.
+            Err(_) => continue,
+        };
+
+        let subject = tag.message().and_then(|message| message.lines().next());
+
+        if tag.target_id() == commit
+            && subject.is_some_and(|subject| messages.iter().any(|m| m == subject))
+        {
+            return Ok(Some(name.to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Lists the subjects of the commits changing the package since its latest
+/// release, or since the beginning of the history for its first release.
+fn release_notes(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<String, anyhow::Error> {
+    let since = latest_release_commit(args, repo, package)?.map(|(_, commit)| commit);
+
+    let dir = history::package_dir(repo, &package.manifest, in_tree(args, repo))?;
+    let target = target_commit(args, repo)?.id();
+
+    let lines: Vec<String> = history::package_commits(
+        repo,
+        target,
+        since,
+        args.first_parent,
+        dir.as_deref(),
+        &args.scopes,
+        &package.package_name,
+    )?
+    .iter()
+    .map(|commit| format!("- {}", commit.summary().unwrap_or_default()))
+    .collect();
+
+    Ok(lines.join("\n"))
+}
+
+/// Creates a single tag for all packages, listing them in the tag message.
+fn create_combined_tag(
+    args: &AutoTagArgs,
+    packages: &[Package],
+    repo: &Repository,
+    tag_name: &str,
+    remote_refs: &HashSet<String>,
+) -> Result<Release, anyhow::Error> {
+    let mut components: Vec<String> = packages
+        .iter()
+        .map(|package| format!("{} {}", package.name, package.version))
+        .collect();
+    components.sort();
+
+    if args.dry_run {
+        for component in &components {
+            args.print_progress(format!(r#"tag "{tag_name}" would include {component}"#));
+        }
+    }
+
+    let tag_message = match &args.message_file {
+        Some(path) => read_message_file(path)?,
+        None => format!(
+            "automatic release tag of {tag_name}\n\n{}",
+            components.join("\n")
+        ),
+    };
+
+    create_release_ref(args, repo, tag_name, &tag_message, remote_refs)
+}
+
+/// The outcome of creating a release tag or branch.
+enum Release {
+    /// The ref was created or replaced, or would be in dry-run mode.
+    Created(String),
+    Skipped(SkipReason),
+}
+
+/// Creates a tag, or a branch with `--as-branch`, for the target commit
+/// unless it already exists, locally or in `remote_refs`.
+fn create_release_ref(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    tag_name: &str,
+    tag_message: &str,
+    remote_refs: &HashSet<String>,
+) -> Result<Release, anyhow::Error> {
+    let kind = if args.as_branch { "branch" } else { "tag" };
+    let refname = release_refname(args, tag_name);
+
+    let commit = target_commit(args, repo)?;
+
+    let (git_user, git_email) = git_user(args, &commit)?;
+
+    let commit_sha = commit.id();
+
+    let short_commit = short_sha(args, repo, commit_sha);
+
+    let tag_message = &match provenance(args, commit_sha) {
+        Some(provenance) => format!("{tag_message}\n\nProvenance: {provenance}"),
+        None => tag_message.to_string(),
+    };
+
+    let tag_type = tag_type(args, repo)?;
+
+    let exists = release_exists(args, repo, tag_name)?;
+
+    // With `--preserve-message`, tags are moved without changing their message.
+    let preserved = match tag_type {
+        TagType::Annotated if exists && args.preserve_message && !args.as_branch => repo
+            .revparse_single(&refname)?
+            .peel_to_tag()
+            .ok()
+            .and_then(|tag| tag.message().map(str::to_string)),
+        _ => None,
+    };
+    let tag_message = preserved.as_ref().unwrap_or(tag_message);
+
+    if exists {
+        let message = match tag_type {
+            TagType::Annotated => Some(tag_message.as_str()),
+            TagType::Lightweight => None,
+        };
+
+        if release_up_to_date(
+            args, repo, tag_name, &commit, message, &git_user, &git_email,
+        )? {
+            args.print_existing(format!(r#"{kind} "{tag_name}" is up to date, skipping..."#));
+            return Ok(Release::Skipped(SkipReason::Unchanged));
+        }
+
+        if !args.force {
+            args.print_existing(format!(
+                r#"{kind} "{tag_name}" already exists, skipping..."#
+            ));
+            return Ok(Release::Skipped(SkipReason::AlreadyExists));
+        }
+    } else if remote_refs.contains(&refname) && !args.force {
+        // Pushing it would be rejected, with `--force` it is replaced instead.
+        args.print_existing(format!(
+            r#"{kind} "{tag_name}" already exists on the remote, skipping..."#
+        ));
+        return Ok(Release::Skipped(SkipReason::AlreadyExists));
+    } else if let Some(existing) = differing_in_case(args, repo, tag_name, remote_refs)? {
+        args.print(format!(
+            r#"warning: {kind} "{existing}" only differs in case from "{tag_name}", skipping..."#
+        ));
+        return Ok(Release::Skipped(SkipReason::AlreadyExists));
+    }
+
+    let (verb, past) = if exists {
+        ("replace", "replaced")
+    } else {
+        ("create", "created")
+    };
+
+    if args.as_branch {
+        if args.dry_run {
+            args.print_progress(format!(
+                r#"would {verb} branch "{tag_name}" for "{short_commit}""#
+            ));
+            return Ok(Release::Created(refname));
+        }
+
+        repo.branch(tag_name, &commit, args.force)?;
+        args.print_progress(format!(r#"{} branch "{}""#, past, tag_name));
+
+        return Ok(Release::Created(refname));
+    }
+
+    if args.dry_run {
+        match tag_type {
+            TagType::Annotated => {
+                let summary = tag_message.lines().next().unwrap_or_default();
+                args.print_progress(format!(
+                    r#"would {verb} tag "{tag_name}" for "{short_commit}" with message "{summary}" as {git_user} ({git_email})"#
+                ));
+            }
+            TagType::Lightweight => {
+                args.print_progress(format!(
+                    r#"would {verb} lightweight tag "{tag_name}" for "{short_commit}""#
+                ));
+            }
+        }
+        return Ok(Release::Created(refname));
+    }
+
+    match tag_type {
+        TagType::Annotated => {
+            repo.tag(
+                tag_name,
+                commit.as_object(),
+                &tagger(args, &commit)?,
+                tag_message,
+                args.force,
+            )?;
+        }
+        TagType::Lightweight => {
+            repo.tag_lightweight(tag_name, commit.as_object(), args.force)?;
+        }
+    }
+
+    args.print_progress(format!(r#"{} tag "{}""#, past, tag_name));
+
+    Ok(Release::Created(refname))
+}
+
+#[derive(serde::Serialize)]
+struct Provenance {
+    builder: &'static str,
+    run_url: String,
+    commit: String,
+}
+
+/// Returns the provenance of the tag as JSON if requested, and the CI system
+/// running `auto-tag` is known.
+fn provenance(args: &AutoTagArgs, commit: Oid) -> Option<String> {
+    if !args.provenance {
+        return None;
+    }
+
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    let (builder, run_url) = if var("GITHUB_ACTIONS").is_some() {
+        let run_url = format!(
+            "{}/{}/actions/runs/{}",
+            var("GITHUB_SERVER_URL")?,
+            var("GITHUB_REPOSITORY")?,
+            var("GITHUB_RUN_ID")?
+        );
+        ("github-actions", run_url)
+    } else if var("GITLAB_CI").is_some() {
+        ("gitlab-ci", var("CI_JOB_URL")?)
+    } else {
+        args.print_progress("no CI metadata found, skipping provenance");
+        return None;
+    };
+
+    let provenance = Provenance {
+        builder,
+        run_url,
+        commit: commit.to_string(),
+    };
+
+    serde_json::to_string(&provenance).ok()
+}
+
+#[derive(Clone, Copy)]
+enum TagType {
+    Lightweight,
+    Annotated,
+}
+
+/// Returns the kind of tags to create, from the command line or the
+/// `auto-tag.tag-type` git config.
+fn tag_type(args: &AutoTagArgs, repo: &Repository) -> Result<TagType, anyhow::Error> {
+    let config = repo.config()?;
+
+    let tag_type = if args.lightweight {
+        TagType::Lightweight
+    } else if args.annotated {
+        TagType::Annotated
+    } else {
+        match config.get_string("auto-tag.tag-type") {
+            Ok(value) => match value.as_str() {
+                "lightweight" => TagType::Lightweight,
+                "annotated" => TagType::Annotated,
+                "signed" => {
+                    return Err(UsageError(
+                        "auto-tag.tag-type is \"signed\", but signing tags is not supported".into(),
+                    )
+                    .into())
+                }
+                _ => {
+                    return Err(UsageError(format!(
+                        "invalid auto-tag.tag-type {value:?}, expected \"lightweight\" or \"annotated\""
+                    ))
+                    .into())
+                }
+            },
+            Err(err) if err.code() == ErrorCode::NotFound => TagType::Annotated,
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    Ok(tag_type)
+}
+
+/// Returns whether the existing release points at the commit, and for tags,
+/// whether it also has the same message and tagger.
+///
+/// A `message` of `None` expects a lightweight tag.
+fn release_up_to_date(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    name: &str,
+    commit: &Commit,
+    message: Option<&str>,
+    git_user: &str,
+    git_email: &str,
+) -> Result<bool, anyhow::Error> {
+    if args.as_branch {
+        let branch = repo.find_branch(name, BranchType::Local)?;
+        return Ok(branch.get().target() == Some(commit.id()));
+    }
+
+    let object = repo.revparse_single(&format!("refs/tags/{name}"))?;
+
+    let (tag, message) = match (object.as_tag(), message) {
+        (Some(tag), Some(message)) => (tag, message),
+        (None, None) => return Ok(object.id() == commit.id()),
+        _ => return Ok(false),
+    };
+
+    let same_tagger = tag
+        .tagger()
+        .map(|tagger| tagger.name() == Some(git_user) && tagger.email() == Some(git_email))
+        .unwrap_or(false);
+
+    Ok(tag.target_id() == commit.id()
+        && tag.message().map(str::trim_end) == Some(message.trim_end())
+        && same_tagger)
+}
+
+/// Pushes the given references to every remote given via `--remote`.
+///
+/// Every remote is attempted even if pushing to another one fails.
+fn push(args: &AutoTagArgs, repo: &Repository, refs: &[String]) -> Result<(), anyhow::Error> {
+    let mut failed = Vec::new();
+
+    for remote in &args.remote {
+        if args.dry_run {
+            args.print_progress(format!(r#"would push {} ref(s) to "{remote}""#, refs.len()));
+            continue;
+        }
+
+        match push_with_timeout(args, repo, remote, refs) {
+            Ok(()) => args.print_progress(format!(r#"pushed {} ref(s) to "{remote}""#, refs.len())),
+            Err(err) => {
+                args.print(format!(r#"failed to push to "{remote}": {err}"#));
+                failed.push(remote.as_str());
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(NetworkError(format!("failed to push to {}", failed.join(", "))).into());
+    }
+
+    Ok(())
+}
+
+/// Pushes to the remote, giving up after `--network-timeout`.
+fn push_with_timeout(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    remote: &str,
+    refs: &[String],
+) -> Result<(), anyhow::Error> {
+    let remote = remote.to_string();
+    let refs = refs.to_vec();
+    let force = args.force;
+    let proxy = args.proxy.clone();
+
+    with_timeout(args, repo, move |repo| {
+        push_to_remote(repo, &remote, &refs, force, proxy.as_deref())
+    })
+}
+
+/// Runs a network operation, giving up after `--network-timeout`.
+///
+/// git2 offers no way to cancel network operations, so they are run in a
+/// separate thread with their own handle of the repository, that is left
+/// behind on timeout.
+fn with_timeout<T: Send + 'static>(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    operation: impl FnOnce(&Repository) -> Result<T, anyhow::Error> + Send + 'static,
+) -> Result<T, anyhow::Error> {
+    if args.network_timeout == 0 {
+        return operation(repo);
+    }
+
+    let path = repo.path().to_path_buf();
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let result = Repository::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|repo| operation(&repo));
+        let _ = sender.send(result);
+    });
+
+    match receiver.recv_timeout(std::time::Duration::from_secs(args.network_timeout)) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(anyhow!("timed out after {}s", args.network_timeout)),
+        Err(RecvTimeoutError::Disconnected) => Err(anyhow!("interrupted")),
+    }
+}
+
+/// Makes sure that the remotes given via `--remote` exist.
+fn check_remotes(args: &AutoTagArgs, repo: &Repository) -> Result<(), anyhow::Error> {
+    for remote in &args.remote {
+        if let Err(err) = repo.find_remote(remote) {
+            if err.code() == ErrorCode::NotFound || err.code() == ErrorCode::InvalidSpec {
+                return Err(UsageError(format!(r#"the remote "{remote}" does not exist"#)).into());
+            }
+            return Err(err.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the names of all refs on the remotes given via `--remote`.
+fn remote_refs(args: &AutoTagArgs, repo: &Repository) -> Result<HashSet<String>, anyhow::Error> {
+    let mut refs = HashSet::new();
+
+    for remote in &args.remote {
+        let name = remote.clone();
+        let proxy = args.proxy.clone();
+
+        let listed = with_timeout(args, repo, move |repo| {
+            let mut remote = repo.find_remote(&name)?;
+            let connection = remote.connect_auth(
+                Direction::Fetch,
+                Some(remote_callbacks(repo)),
+                Some(proxy_options(proxy.as_deref())),
+            )?;
+
+            Ok(connection
+                .list()?
+                .iter()
+                .map(|head| head.name().to_string())
+                .collect::<Vec<_>>())
+        })
+        .map_err(|err| NetworkError(format!(r#"failed to list the refs of "{remote}": {err}"#)))?;
+
+        refs.extend(listed);
+    }
+
+    Ok(refs)
+}
+
+fn push_to_remote(
+    repo: &Repository,
+    remote: &str,
+    refs: &[String],
+    force: bool,
+    proxy: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    let force = if force { "+" } else { "" };
+    let refspecs: Vec<String> = refs.iter().map(|r| format!("{force}{r}:{r}")).collect();
+
+    let mut rejected = Vec::new();
+
+    {
+        let mut callbacks = remote_callbacks(repo);
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                rejected.push(format!("{refname} ({status})"));
+            }
+            Ok(())
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        options.proxy_options(proxy_options(proxy));
+
+        remote.push(&refspecs, Some(&mut options))?;
+    }
+
+    if !rejected.is_empty() {
+        return Err(anyhow!("rejected {}", rejected.join(", ")));
+    }
+
+    Ok(())
+}
+
+/// Returns the proxy given via `--proxy`, or detects it from the git config
+/// and the environment.
+fn proxy_options(proxy: Option<&str>) -> ProxyOptions<'_> {
+    let mut options = ProxyOptions::new();
+
+    match proxy {
+        Some(url) => options.url(url),
+        None => options.auto(),
     };
 
-    let args = AutoTagArgs::parse();
+    options
+}
 
-    for arg in &args.paths {
-        for entry in WalkDir::new(arg) {
-            let entry = match entry {
-                Ok(e) => e,
-                Err(err) => {
-                    println!("cannot access file: {}", err);
-                    continue;
-                }
-            };
+/// Creates callbacks that provide credentials for remote operations.
+///
+/// Credentials are looked up from the SSH agent, the `GIT_TOKEN` or
+/// `GITHUB_TOKEN` environment variables and the configured git credential
+/// helpers, each of them being tried at most once.
+fn remote_callbacks(repo: &Repository) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
 
-            if entry
-                .path()
-                .file_name()
-                .map(|f| f == "Cargo.toml")
-                .unwrap_or(false)
-            {
-                if let Err(err) = process_cargo_toml(&args, entry.path(), &repo) {
-                    println!("failed to process {:?}: {}", entry.path(), err);
-                }
-            } else if entry
-                .path()
-                .file_name()
-                .map(|f| f == "package.json")
-                .unwrap_or(false)
-            {
-                if let Err(err) = process_package_json(&args, entry.path(), &repo) {
-                    println!("failed to process {:?}: {}", entry.path(), err);
-                }
-            } else if entry
-                .path()
-                .file_name()
-                .map(|f| f == "pyproject.toml")
-                .unwrap_or(false)
-            {
-                if let Err(err) = process_pyproject_toml(&args, entry.path(), &repo) {
-                    println!("failed to process {:?}: {}", entry.path(), err);
+    let mut tried_agent = false;
+    let mut tried_token = false;
+    let mut tried_helper = false;
+
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) && !tried_agent {
+            tried_agent = true;
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if !tried_token {
+                tried_token = true;
+
+                if let Some(token) = ["GIT_TOKEN", "GITHUB_TOKEN"]
+                    .iter()
+                    .find_map(|var| std::env::var(var).ok())
+                {
+                    return Cred::userpass_plaintext(username.unwrap_or("x-access-token"), &token);
                 }
             }
+
+            if !tried_helper {
+                tried_helper = true;
+                return Cred::credential_helper(&repo.config()?, url, username);
+            }
+        }
+
+        Err(git2::Error::from_str("no usable credentials found"))
+    });
+
+    callbacks
+}
+
+/// Returns the full name of the release tag, or branch with `--as-branch`.
+fn release_refname(args: &AutoTagArgs, name: &str) -> String {
+    if args.as_branch {
+        format!("refs/heads/{name}")
+    } else {
+        format!("refs/tags/{name}")
+    }
+}
+
+/// Restores the refs created or replaced by an aborted `--atomic` run to
+/// their previous targets, deleting the ones that did not exist.
+fn rollback(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    previous: &[(String, Option<Oid>)],
+) -> Result<(), anyhow::Error> {
+    for (refname, target) in previous.iter().rev() {
+        if args.dry_run {
+            args.print_progress(format!(r#"would roll back "{refname}""#));
+            continue;
+        }
+
+        match target {
+            Some(target) => {
+                repo.reference(refname, *target, true, "auto-tag: roll back")?;
+            }
+            None => repo.find_reference(refname)?.delete()?,
         }
+
+        args.print_progress(format!(r#"rolled back "{refname}""#));
     }
 
     Ok(())
 }
 
-fn process_package_json(
+/// Returns whether a release tag, or a branch with `--as-branch`, exists with the given name.
+fn release_exists(
     args: &AutoTagArgs,
-    path: &Path,
     repo: &Repository,
-) -> Result<(), anyhow::Error> {
-    let json_str = std::fs::read_to_string(path)?;
-    let package_json: serde_json::Value = serde_json::from_str(&json_str)?;
+    name: &str,
+) -> Result<bool, anyhow::Error> {
+    if !args.as_branch {
+        return Ok(!repo.tag_names(Some(name))?.is_empty());
+    }
+
+    match repo.find_branch(name, BranchType::Local) {
+        Ok(_) => Ok(true),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns an existing release, locally or in `remote_refs`, whose name only
+/// differs in case from the given one with `--ignore-case`.
+fn differing_in_case(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    name: &str,
+    remote_refs: &HashSet<String>,
+) -> Result<Option<String>, anyhow::Error> {
+    if !args.ignore_case {
+        return Ok(None);
+    }
+
+    let prefix = release_refname(args, "");
+    let remote_names = remote_refs.iter().filter_map(|refname| {
+        let name = refname.strip_prefix(&prefix)?;
+        Some(name.strip_suffix("^{}").unwrap_or(name).to_string())
+    });
+
+    let lowercase = name.to_lowercase();
+
+    Ok(release_names(args, repo)?
+        .into_iter()
+        .chain(remote_names)
+        .find(|existing| existing != name && existing.to_lowercase() == lowercase))
+}
+
+/// Returns the names of all tags, or all local branches with `--as-branch`.
+fn release_names(args: &AutoTagArgs, repo: &Repository) -> Result<Vec<String>, anyhow::Error> {
+    if !args.as_branch {
+        return Ok(repo
+            .tag_names(None)?
+            .iter()
+            .flatten()
+            .map(str::to_string)
+            .collect());
+    }
+
+    let mut names = Vec::new();
+
+    for branch in repo.branches(Some(BranchType::Local))? {
+        if let Some(name) = branch?.0.name()? {
+            names.push(name.to_string());
+        }
+    }
+
+    Ok(names)
+}
 
-    if let Some(true) = package_json["autoTag"]["enabled"].as_bool() {
-        let name = package_json["name"]
-            .as_str()
-            .ok_or_else(|| anyhow!("package name not found"))?
-            .replace('@', "")
-            .replace('/', "__");
+/// Attaches the note given via `--note` or `--note-from` to the tagged commit.
+fn add_note(args: &AutoTagArgs, repo: &Repository) -> Result<(), anyhow::Error> {
+    let note = match (&args.note, &args.note_from) {
+        (Some(note), _) => note.clone(),
+        (None, Some(path)) => std::fs::read_to_string(path)?,
+        (None, None) => return Ok(()),
+    };
 
-        let version = package_json["version"]
-            .as_str()
-            .ok_or_else(|| anyhow!("package version not found"))?;
+    let commit_sha = target_commit(args, repo)?.id();
+    let short_commit = short_sha(args, repo, commit_sha);
+    let notes_ref = &args.notes_ref;
 
-        let tag_name = format!("release-{name}-{version}");
-        create_tag(args, &name, version, &tag_name, repo)?;
+    if args.dry_run {
+        args.print_progress(format!(
+            r#"would add note to "{short_commit}" in "{notes_ref}""#
+        ));
+        return Ok(());
     }
 
+    let note = match (args.note_mode, repo.find_note(Some(notes_ref), commit_sha)) {
+        (NoteMode::Append, Ok(existing)) => match existing.message() {
+            Some(message) => format!("{}\n\n{}", message.trim_end(), note.trim_end()),
+            None => note,
+        },
+        _ => note,
+    };
+    let note = format!("{}\n", note.trim_end());
+
+    let (git_user, git_email) = git_user(args, &target_commit(args, repo)?)?;
+    let signature = Signature::now(&git_user, &git_email)?;
+
+    repo.note(
+        &signature,
+        &signature,
+        Some(notes_ref),
+        commit_sha,
+        &note,
+        true,
+    )?;
+
+    args.print_progress(format!(
+        r#"added note to "{short_commit}" in "{notes_ref}""#
+    ));
+
     Ok(())
 }
 
-fn process_cargo_toml(
+#[derive(serde::Serialize)]
+struct DiffEntry {
+    name: String,
+    manifest_version: String,
+    latest_tag_version: Option<String>,
+    tagged: bool,
+}
+
+fn diff(
     args: &AutoTagArgs,
-    path: &Path,
+    diff_args: &SearchArgs,
     repo: &Repository,
 ) -> Result<(), anyhow::Error> {
-    let toml_str = std::fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+
+    for package in find_packages(args, repo, &diff_args.paths)?.packages {
+        let tagged = release_exists(args, repo, &package.tag_name(args))?
+            || equivalent_release(args, repo, &package)?.is_some();
+
+        entries.push(DiffEntry {
+            latest_tag_version: latest_release(args, repo, &package)?
+                .map(|(version, _)| version.to_string()),
+            name: package.name,
+            manifest_version: package.version,
+            tagged,
+        });
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            for entry in &entries {
+                args.print_output(format!(
+                    "{}: manifest {}, latest tag {}, {}",
+                    entry.name,
+                    entry.manifest_version,
+                    entry.latest_tag_version.as_deref().unwrap_or("none"),
+                    if entry.tagged { "OK" } else { "MISSING" }
+                ))?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Csv => args.print_data(&entries)?,
+    }
+
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct ExistingRelease {
+    name: String,
+    version: String,
+    tag: String,
+    commit: String,
+}
+
+/// Lists the releases named by the templates and prefixes given on the
+/// command line.
+fn list_existing(args: &AutoTagArgs, repo: &Repository) -> Result<(), anyhow::Error> {
+    let mut releases = Vec::new();
 
-    let cargo_toml: toml::Value = toml::from_str(&toml_str)?;
+    for tag in release_names(args, repo)? {
+        let (name, version) = match scheme_release(args, &tag) {
+            Some((name, version)) => (name.to_string(), semver::Version::parse(version)?),
+            None => continue,
+        };
+        let commit = repo.revparse_single(&tag)?.peel_to_commit()?.id();
 
-    let auto_tag = cargo_toml
-        .get("package")
-        .and_then(|package| package.get("metadata"))
-        .and_then(|metadata| metadata.get("auto-tag"))
-        .and_then(|tag| tag.get("enabled"))
-        .and_then(|auto_tag| auto_tag.as_bool());
+        releases.push((name, version, tag, commit));
+    }
 
-    if let Some(true) = auto_tag {
-        let name = cargo_toml
-            .get("package")
-            .and_then(|package| package.get("name"))
-            .and_then(|name| name.as_str())
-            .ok_or_else(|| anyhow!("package name not found"))?;
+    releases.sort_by(|(a, a_version, ..), (b, b_version, ..)| {
+        a.cmp(b)
+            .then_with(|| precedence(a_version).cmp(&precedence(b_version)))
+    });
 
-        let version = cargo_toml
-            .get("package")
-            .and_then(|package| package.get("version"))
-            .and_then(|version| version.as_str())
-            .ok_or_else(|| anyhow!("package version not found"))?;
+    match args.output {
+        OutputFormat::Text => {
+            for (name, version, tag, commit) in &releases {
+                args.print_output(format!(
+                    "{name} {version}: {tag} ({})",
+                    short_sha(args, repo, *commit)
+                ))?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Csv => {
+            let releases: Vec<ExistingRelease> = releases
+                .into_iter()
+                .map(|(name, version, tag, commit)| ExistingRelease {
+                    name,
+                    version: version.to_string(),
+                    tag,
+                    commit: commit.to_string(),
+                })
+                .collect();
 
-        let tag_name = format!("release-{name}-{version}");
-        create_tag(args, name, version, &tag_name, repo)?;
+            args.print_data(&releases)?
+        }
     }
 
     Ok(())
 }
 
-fn process_pyproject_toml(
+/// Returns the committer date of the commit as `YYYYMMDD`, in the time zone
+/// of the committer.
+fn commit_date(commit: &Commit) -> String {
+    let time = commit.committer().when();
+    let days = (time.seconds() + i64::from(time.offset_minutes()) * 60).div_euclid(86400);
+
+    // Converts days since the epoch to a date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}{month:02}{day:02}")
+}
+
+/// Returns the tag name without the date added by `--date-prefix`.
+///
+/// Returns `None` if the tag has no date prefix.
+fn strip_date_prefix<'t>(args: &AutoTagArgs, tag: &'t str) -> Option<&'t str> {
+    if !args.date_prefix {
+        return Some(tag);
+    }
+
+    let (date, rest) = tag.split_once('-')?;
+
+    if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Returns the versions of the existing release tags of a package, along with
+/// the names of the tags.
+///
+/// Tags with versions that are not valid semver are ignored.
+fn releases(
     args: &AutoTagArgs,
-    path: &Path,
     repo: &Repository,
-) -> Result<(), anyhow::Error> {
-    let toml_str = std::fs::read_to_string(path)?;
+    package: &Package,
+) -> Result<Vec<(semver::Version, String)>, anyhow::Error> {
+    const VERSION_MARKER: &str = "\0";
+
+    let pattern = package.tag_name_for(args, VERSION_MARKER);
+    let (prefix, suffix) = match pattern.split_once(VERSION_MARKER) {
+        Some(parts) => parts,
+        None => return Ok(Vec::new()),
+    };
+
+    let releases = release_names(args, repo)?
+        .into_iter()
+        .filter_map(|tag| {
+            let version = strip_date_prefix(args, &tag)?
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?;
+            let version = semver::Version::parse(version).ok()?;
+            Some((version, tag))
+        })
+        .collect();
+
+    Ok(releases)
+}
+
+/// Derives the version of the package from the closest release tag
+/// reachable from the commit being tagged, like `git describe --tags`.
+///
+/// On the tagged commit the version is that of the tag, otherwise it is a
+/// development version after it with the number of commits since, e.g.
+/// `1.2.4-dev.3` three commits after `1.2.3`.
+///
+/// Returns `None` if no release tags are reachable.
+fn scm_version(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<Option<String>, anyhow::Error> {
+    let target = target_commit(args, repo)?.id();
+    let mut closest: Option<(usize, semver::Version)> = None;
 
-    let pyproject_toml: toml::Value = toml::from_str(&toml_str)?;
+    for (version, tag) in releases(args, repo, package)? {
+        let commit = repo.revparse_single(&tag)?.peel_to_commit()?.id();
 
-    let auto_tag = pyproject_toml
-        .get("tool")
-        .and_then(|package| package.get("auto-tag"))
-        .and_then(|tag| tag.get("enabled"))
-        .and_then(|auto_tag| auto_tag.as_bool());
+        if commit != target && !repo.graph_descendant_of(target, commit)? {
+            continue;
+        }
 
-    if let Some(true) = auto_tag {
-        let name = pyproject_toml
-            .get("tool")
-            .and_then(|tool| tool.get("poetry"))
-            .and_then(|poetry| poetry.get("name"))
-            .and_then(|name| name.as_str())
-            .ok_or_else(|| anyhow!("package name not found"))?;
+        let (distance, _) = repo.graph_ahead_behind(target, commit)?;
 
-        let version = pyproject_toml
-            .get("tool")
-            .and_then(|tool| tool.get("poetry"))
-            .and_then(|poetry| poetry.get("version"))
-            .and_then(|version| version.as_str())
-            .ok_or_else(|| anyhow!("package version not found"))?;
+        let closer = closest
+            .as_ref()
+            .is_none_or(|(closest_distance, closest_version)| {
+                (distance, std::cmp::Reverse(precedence(&version)))
+                    < (
+                        *closest_distance,
+                        std::cmp::Reverse(precedence(closest_version)),
+                    )
+            });
 
-        let tag_name = format!("release-{name}-{version}");
-        create_tag(args, name, version, &tag_name, repo)?;
+        if closer {
+            closest = Some((distance, version));
+        }
     }
 
-    Ok(())
+    let (distance, mut version) = match closest {
+        Some(closest) => closest,
+        None => return Ok(None),
+    };
+
+    if distance > 0 {
+        let pre = if version.pre.is_empty() {
+            version.patch += 1;
+            format!("dev.{distance}")
+        } else {
+            format!("{}.dev.{distance}", version.pre)
+        };
+
+        version.pre = semver::Prerelease::new(&pre)?;
+        version.build = semver::BuildMetadata::EMPTY;
+    }
+
+    Ok(Some(version.to_string()))
 }
 
-fn create_tag(
+/// Returns the version without build metadata, which is ignored when
+/// comparing versions.
+fn precedence(version: &semver::Version) -> (u64, u64, u64, &semver::Prerelease) {
+    (version.major, version.minor, version.patch, &version.pre)
+}
+
+/// Returns the package name in a release tag named by the templates and
+/// prefixes given on the command line.
+fn scheme_name<'t>(args: &AutoTagArgs, tag: &'t str) -> Option<&'t str> {
+    scheme_release(args, tag).map(|(name, _)| name)
+}
+
+/// Returns the package name and version in a release tag named by the
+/// templates and prefixes given on the command line.
+fn scheme_release<'t>(args: &AutoTagArgs, tag: &'t str) -> Option<(&'t str, &'t str)> {
+    let tag = strip_date_prefix(args, tag)?;
+
+    const ECOSYSTEMS: &[Ecosystem] = &[
+        Ecosystem::Cargo,
+        Ecosystem::Npm,
+        Ecosystem::Python,
+        Ecosystem::Deno,
+        Ecosystem::External,
+    ];
+
+    ECOSYSTEMS.iter().find_map(|&ecosystem| {
+        // Only the name and version are left as placeholders.
+        let version = format!("{}{{version}}", args.version_prefix(ecosystem));
+        let (prefix, _) = render_template(
+            args.tag_prefix(ecosystem),
+            &[("name", "{name}"), ("version", &version)],
+        );
+        let (template, _) = render_template(
+            args.tag_template(ecosystem),
+            &[
+                ("prefix", &prefix),
+                ("name", "{name}"),
+                ("version", &version),
+            ],
+        );
+
+        let (before_name, rest) = template.split_once("{name}")?;
+        let (between, after_version) = rest.split_once("{version}")?;
+
+        let rest = tag.strip_prefix(before_name)?.strip_suffix(after_version)?;
+
+        // Names and versions can both contain the separator, so the name ends at
+        // the first separator followed by a valid version.
+        rest.match_indices(between).find_map(|(i, _)| {
+            let name = &rest[..i];
+            let version = &rest[i + between.len()..];
+
+            if name.is_empty() || semver::Version::parse(version).is_err() {
+                return None;
+            }
+
+            Some((name, version))
+        })
+    })
+}
+
+/// Deletes the release tags of packages that are no longer found.
+///
+/// The tags of `disabled` packages are kept, their manifests still exist.
+fn prune(
     args: &AutoTagArgs,
-    name: &str,
-    version: &str,
-    tag_name: &str,
     repo: &Repository,
+    packages: &[Package],
+    disabled: &[Package],
 ) -> Result<(), anyhow::Error> {
-    if !repo.tag_names(Some(tag_name))?.is_empty() {
-        println!(r#"tag "{}" already exists, skipping..."#, tag_name);
+    let kind = if args.as_branch { "branch" } else { "tag" };
+
+    let mut current = HashSet::new();
+
+    for package in packages {
+        for (_, tag) in releases(args, repo, package)? {
+            current.insert(tag);
+        }
+    }
+
+    let names: HashSet<&str> = packages
+        .iter()
+        .chain(disabled)
+        .map(|package| package.name.as_str())
+        .collect();
+
+    let stale: Vec<String> = release_names(args, repo)?
+        .into_iter()
+        .filter(|tag| !current.contains(tag))
+        .filter(|tag| scheme_name(args, tag).is_some_and(|name| !names.contains(name)))
+        .collect();
+
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    if args.dry_run {
+        for tag in &stale {
+            args.print_progress(format!(r#"would delete {kind} "{tag}""#));
+        }
         return Ok(());
     }
 
-    let tag_message = format!("automatic release tag of {} ({})", name, version);
+    if !args.yes && !confirm_prune(args, kind, &stale)? {
+        args.print(format!("not deleting any {kind}"));
+        return Ok(());
+    }
 
-    let git_user = &args.git_user_name;
-    let git_email = &args.git_user_email;
+    for tag in &stale {
+        if args.as_branch {
+            repo.find_branch(tag, BranchType::Local)?.delete()?;
+        } else {
+            repo.tag_delete(tag)?;
+        }
 
-    let commit = if let Some(sha) = &args.commit {
-        repo.find_commit(Oid::from_str(sha)?)?
-    } else {
-        repo.head()?.peel_to_commit()?
+        args.print_progress(format!(r#"deleted {kind} "{tag}""#));
+    }
+
+    Ok(())
+}
+
+/// Asks on the terminal whether the stale releases should be deleted.
+///
+/// Whether stdin is a terminal is checked before any tag is created.
+fn confirm_prune(args: &AutoTagArgs, kind: &str, stale: &[String]) -> Result<bool, anyhow::Error> {
+    for tag in stale {
+        args.print(format!(r#"{kind} "{tag}" belongs to no package"#));
+    }
+
+    eprint!("delete {} {kind}(s)? [y/N] ", stale.len());
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Returns the highest version among the existing release tags of a package,
+/// along with the name of its tag.
+fn latest_release(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<Option<(semver::Version, String)>, anyhow::Error> {
+    let latest = releases(args, repo, package)?
+        .into_iter()
+        .max_by(|(a, _), (b, _)| precedence(a).cmp(&precedence(b)));
+
+    Ok(latest)
+}
+
+/// Returns the latest release tag of the package along with the commit it
+/// points at.
+fn latest_release_commit(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<Option<(String, Oid)>, anyhow::Error> {
+    match latest_release(args, repo, package)? {
+        Some((_, tag)) => {
+            let commit = repo.revparse_single(&tag)?.peel_to_commit()?.id();
+            Ok(Some((tag, commit)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns whether the version field in the manifest of the package differs
+/// between the commit being tagged and the baseline given by
+/// `--version-baseline`, or the first parent of the commit.
+///
+/// The manifests are read from the trees of both commits, so other changes
+/// to them do not count. Packages without a readable version in the manifest
+/// of the commit are always considered changed.
+fn version_changed(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<bool, anyhow::Error> {
+    let target = target_commit(args, repo)?;
+
+    let baseline = match &args.version_baseline {
+        Some(revspec) => repo.revparse_single(revspec)?.peel_to_commit()?,
+        None => match target.parents().next() {
+            Some(parent) => parent,
+            None => return Ok(true),
+        },
     };
 
-    let commit_sha = commit.id();
+    let file_name = package
+        .manifest
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", log_path(&package.manifest)))?;
+    let manifest = match history::package_dir(repo, &package.manifest, in_tree(args, repo))? {
+        Some(dir) => PathBuf::from(dir).join(file_name),
+        None => PathBuf::from(file_name),
+    };
 
-    if args.dry_run {
-        println!(
-            r#"would create tag "{tag_name}" for "{commit_sha}" with message "{tag_message}" as {git_user} ({git_email})"#
+    let version = |tree: &git2::Tree| -> Result<Option<String>, AutoTagError> {
+        let read = |path: &Path| read_manifest(repo, Some(tree), path);
+
+        match read(&manifest) {
+            Ok(contents) => {
+                sources::manifest_version(args, package.ecosystem, &manifest, &contents, &read)
+            }
+            // The package is new.
+            Err(AutoTagError::Git(err)) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    };
+
+    match version(&target.tree()?)? {
+        Some(current) => Ok(version(&baseline.tree()?)?.as_ref() != Some(&current)),
+        None => Ok(true),
+    }
+}
+
+/// Returns the name of an existing release tag of the current version of the
+/// package that only differs in build metadata, e.g. `1.0.0+a` for `1.0.0+b`.
+fn equivalent_release(
+    args: &AutoTagArgs,
+    repo: &Repository,
+    package: &Package,
+) -> Result<Option<String>, anyhow::Error> {
+    let version = match semver::Version::parse(&package.version) {
+        Ok(version) => version,
+        Err(_) => return Ok(None),
+    };
+
+    let tag_name = package.tag_name(args);
+
+    let equivalent = releases(args, repo, package)?
+        .into_iter()
+        .find(|(other, tag)| precedence(other) == precedence(&version) && *tag != tag_name)
+        .map(|(_, tag)| tag);
+
+    Ok(equivalent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sources::tests::args;
+
+    fn package(manifest: &str, name: &str) -> Package {
+        Package {
+            ecosystem: Ecosystem::Cargo,
+            manifest: PathBuf::from(manifest),
+            package_name: name.to_string(),
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            template: None,
+            prefix: None,
+        }
+    }
+
+    #[test]
+    fn tag_names_differing_in_case_collide() {
+        let packages = [
+            package("a/Cargo.toml", "foo"),
+            package("b/Cargo.toml", "Foo"),
+            package("c/Cargo.toml", "bar"),
+        ];
+
+        let mut collisions = case_collisions(&args(&[]), &packages);
+        assert_eq!(collisions.len(), 1);
+
+        collisions[0].sort();
+        assert_eq!(
+            collisions[0],
+            [
+                ("release-Foo-1.0.0".to_string(), Path::new("b/Cargo.toml")),
+                ("release-foo-1.0.0".to_string(), Path::new("a/Cargo.toml")),
+            ]
         );
-        return Ok(());
     }
 
-    repo.tag(
-        tag_name,
-        commit.as_object(),
-        &Signature::now(git_user, git_email)?,
-        &tag_message,
-        false,
-    )?;
+    #[test]
+    fn identical_tag_names_do_not_collide_in_case() {
+        let packages = [
+            package("a/Cargo.toml", "foo"),
+            package("b/Cargo.toml", "foo"),
+        ];
 
-    println!(r#"created tag "{}""#, tag_name);
+        assert!(case_collisions(&args(&[]), &packages).is_empty());
+    }
 
-    Ok(())
+    #[test]
+    fn substituted_values_are_not_rendered_again() {
+        std::env::set_var("AUTO_TAG_TEST_CHANNEL", "{version}");
+
+        let (tag_name, unset) = render_template(
+            "${AUTO_TAG_TEST_CHANNEL}/{name}-{version}${AUTO_TAG_TEST_UNSET}",
+            &[("name", "{version}"), ("version", "1.0.0")],
+        );
+
+        assert_eq!(tag_name, "{version}/{version}-1.0.0");
+        assert_eq!(unset, ["AUTO_TAG_TEST_UNSET"]);
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let (tag_name, _) = render_template("{{name}}-{other}-{", &[("name", "foo")]);
+
+        assert_eq!(tag_name, "{foo}-{other}-{");
+    }
+
+    #[test]
+    fn qualified_path_separators() {
+        assert_eq!(qualified_path("crates/foo", None), "crates/foo");
+        assert_eq!(qualified_path(r"crates\foo", None), "crates/foo");
+        assert_eq!(qualified_path(r"a\b/c", Some(2)), "b/c");
+        assert_eq!(
+            qualified_path(r"my crates\foo.lock", None),
+            "my-crates/foo-lock"
+        );
+        assert_eq!(qualified_path("", None), "");
+    }
 }
@@ -0,0 +1,2039 @@
+//! Detection of packages in the manifests of the supported ecosystems.
+
+use crate::error::{AutoTagError, UsageError};
+use crate::{log_path, AutoTagArgs, Ecosystem, Package, SkipReason};
+use anyhow::anyhow;
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A kind of manifest that packages can be read from.
+pub(crate) trait PackageSource {
+    /// Returns whether the file at the path is a manifest of this kind.
+    fn matches(&self, path: &Path) -> bool;
+
+    /// Reads the package from the contents of the manifest at the path.
+    ///
+    /// Other files that the package depends on, such as the workspace root
+    /// of Cargo packages, are read with `read`.
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        contents: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError>;
+
+    /// The ecosystem of the packages read from this source.
+    fn ecosystem(&self) -> Ecosystem;
+
+    /// The file names or globs of the recognized manifests.
+    fn file_names(&self) -> Vec<String>;
+
+    /// The key in the manifest that enables tagging.
+    fn enable_key(&self) -> &str;
+
+    /// Returns the contents of the manifest with tagging enabled.
+    ///
+    /// Returns `None` if tagging is already enabled.
+    fn enable(&self, contents: &str) -> Result<Option<String>, anyhow::Error>;
+}
+
+/// Reads a file from the same place as the manifests.
+pub(crate) type ReadFile<'a> = dyn Fn(&Path) -> Result<String, AutoTagError> + 'a;
+
+/// A package read from a manifest.
+pub(crate) enum Extracted {
+    Package(Package),
+    /// The version of the package is derived from its release tags, with
+    /// `--scm-version`.
+    ScmVersion(Package),
+    /// The manifest has no package to tag.
+    Skipped(SkipReason),
+}
+
+/// The workspace roots found while reading manifests, so that they are only
+/// looked up once for all members.
+#[derive(Default)]
+pub(crate) struct WorkspaceCache {
+    /// The parsed TOML manifests, `None` if they cannot be read.
+    manifests: RefCell<HashMap<PathBuf, Option<Rc<toml::Value>>>>,
+    /// The closest `Cargo.toml` with a `[workspace]` table in or above each
+    /// directory.
+    cargo_roots: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl WorkspaceCache {
+    /// Returns the parsed TOML manifest at the path, or `None` if it cannot be
+    /// read.
+    fn manifest(
+        &self,
+        path: &Path,
+        read: &ReadFile,
+    ) -> Result<Option<Rc<toml::Value>>, AutoTagError> {
+        if let Some(manifest) = self.manifests.borrow().get(path) {
+            return Ok(manifest.clone());
+        }
+
+        let manifest = match read(path) {
+            Ok(contents) => Some(Rc::new(toml::from_str(&contents)?)),
+            Err(_) => None,
+        };
+
+        self.manifests
+            .borrow_mut()
+            .insert(path.to_path_buf(), manifest.clone());
+
+        Ok(manifest)
+    }
+
+    /// Returns the closest `Cargo.toml` with a `[workspace]` table in the
+    /// directory or its parents.
+    ///
+    /// Every directory on the way is remembered, so members of the same
+    /// workspace only look up their own directory.
+    fn cargo_root(&self, dir: &Path, read: &ReadFile) -> Result<Option<PathBuf>, AutoTagError> {
+        if let Some(root) = self.cargo_roots.borrow().get(dir) {
+            return Ok(root.clone());
+        }
+
+        let manifest = dir.join("Cargo.toml");
+
+        let root = match self.manifest(&manifest, read)? {
+            Some(candidate) if candidate.get("workspace").is_some() => Some(manifest),
+            _ => match dir.parent() {
+                Some(parent) => self.cargo_root(parent, read)?,
+                None => None,
+            },
+        };
+
+        self.cargo_roots
+            .borrow_mut()
+            .insert(dir.to_path_buf(), root.clone());
+
+        Ok(root)
+    }
+}
+
+/// The file next to a manifest that configures the release of its package
+/// without any keys in the manifest itself.
+pub(crate) const RELEASE_FILE: &str = ".release.toml";
+
+/// The contents of a [`RELEASE_FILE`], taking precedence over the
+/// corresponding keys of the manifest.
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ReleaseFile {
+    enabled: Option<bool>,
+    version_override: Option<String>,
+    tag_template: Option<String>,
+    /// The package name as it appears in tags.
+    name_override: Option<String>,
+}
+
+/// Reads the [`RELEASE_FILE`] next to the manifest, or returns the defaults
+/// if there is none.
+fn release_file(
+    args: &AutoTagArgs,
+    path: &Path,
+    read: &ReadFile,
+) -> Result<ReleaseFile, AutoTagError> {
+    let file = path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(RELEASE_FILE);
+
+    match args.workspace_cache.manifest(&file, read)? {
+        Some(release) => Ok(release.as_ref().clone().try_into()?),
+        None => Ok(ReleaseFile::default()),
+    }
+}
+
+/// Applies the [`RELEASE_FILE`] next to the manifest to the extracted
+/// package.
+///
+/// A version override also replaces versions derived from release tags.
+pub(crate) fn apply_release_file(
+    args: &AutoTagArgs,
+    path: &Path,
+    read: &ReadFile,
+    extracted: Extracted,
+) -> Result<Extracted, AutoTagError> {
+    let (mut package, scm_version) = match extracted {
+        Extracted::Package(package) => (package, false),
+        Extracted::ScmVersion(package) => (package, true),
+        Extracted::Skipped(reason) => return Ok(Extracted::Skipped(reason)),
+    };
+
+    let release = release_file(args, path, read)?;
+
+    if let Some(name) = release.name_override {
+        package.name = name;
+    }
+
+    if let Some(template) = release.tag_template {
+        package.template = Some(template);
+    }
+
+    match release.version_override {
+        Some(version) => {
+            package.version = version;
+            Ok(Extracted::Package(package))
+        }
+        None if scm_version => Ok(Extracted::ScmVersion(package)),
+        None => Ok(Extracted::Package(package)),
+    }
+}
+
+/// Returns only the version field of the manifest, or `None` if it has none.
+///
+/// Versions of Cargo packages inherited from the workspace are read from its
+/// root. Other inherited or dynamic versions are not resolved.
+pub(crate) fn manifest_version(
+    args: &AutoTagArgs,
+    ecosystem: Ecosystem,
+    path: &Path,
+    contents: &str,
+    read: &ReadFile,
+) -> Result<Option<String>, AutoTagError> {
+    let version = match ecosystem {
+        Ecosystem::Cargo => {
+            let cargo_toml: toml::Value = toml::from_str(contents)?;
+            let version = cargo_toml
+                .get("package")
+                .and_then(|package| package.get("version"));
+
+            match version {
+                Some(version) if inherits(version) => {
+                    return cargo_workspace_version(path, &cargo_toml, read);
+                }
+                version => toml_string(version, "package version")?.map(str::to_string),
+            }
+        }
+        Ecosystem::Python => {
+            let pyproject_toml: toml::Value = toml::from_str(contents)?;
+            let version = pyproject_toml
+                .get("project")
+                .and_then(|project| project.get("version"))
+                .or_else(|| {
+                    pyproject_toml
+                        .get("tool")
+                        .and_then(|tool| tool.get("poetry"))
+                        .and_then(|poetry| poetry.get("version"))
+                });
+
+            toml_string(version, "package version")?.map(str::to_string)
+        }
+        Ecosystem::Npm => {
+            let package_json = parse_package_json(args, contents, &["version"])?;
+            package_json["version"].as_str().map(str::to_string)
+        }
+        Ecosystem::Deno => {
+            let deno_json: serde_json::Value = json5::from_str(contents)?;
+            deno_json["version"].as_str().map(str::to_string)
+        }
+        Ecosystem::External => None,
+    };
+
+    Ok(version)
+}
+
+/// Returns the `workspace.package.version` of the workspace given by
+/// `package.workspace`, or of the closest one in or above the directory of the
+/// Cargo manifest, without going through the [`WorkspaceCache`].
+fn cargo_workspace_version(
+    path: &Path,
+    cargo_toml: &toml::Value,
+    read: &ReadFile,
+) -> Result<Option<String>, AutoTagError> {
+    let candidates: Vec<PathBuf> = match explicit_workspace_root(path, cargo_toml) {
+        Some(root_manifest) => vec![root_manifest],
+        None => path
+            .ancestors()
+            .skip(1)
+            .map(|dir| dir.join("Cargo.toml"))
+            .collect(),
+    };
+
+    for manifest in candidates {
+        let contents = match read(&manifest) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+
+        let root: toml::Value = toml::from_str(&contents)?;
+
+        if let Some(workspace) = root.get("workspace") {
+            let version = workspace
+                .get("package")
+                .and_then(|package| package.get("version"));
+
+            return Ok(toml_string(version, "workspace package version")?.map(str::to_string));
+        }
+    }
+
+    Ok(None)
+}
+
+/// All supported package sources.
+pub(crate) const SOURCES: &[&dyn PackageSource] =
+    &[&CargoToml, &PackageJson, &PyprojectToml, &DenoJson];
+
+/// Removes the UTF-8 byte order mark some editors put at the beginning of
+/// files, which the parsers reject.
+pub(crate) fn strip_bom(contents: &str) -> &str {
+    contents.strip_prefix('\u{feff}').unwrap_or(contents)
+}
+
+/// Returns the version given in the manifest, or with
+/// `--version-file-fallback` the contents of the `VERSION` file next to it.
+fn version_or_fallback(
+    args: &AutoTagArgs,
+    path: &Path,
+    version: Option<&str>,
+    read: &ReadFile,
+) -> Result<String, AutoTagError> {
+    if let Some(version) = version {
+        return Ok(version.to_string());
+    }
+
+    if args.version_file_fallback {
+        let file = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join("VERSION");
+
+        if let Ok(contents) = read(&file) {
+            let version = contents.trim();
+
+            if !version.is_empty() {
+                return Ok(version.to_string());
+            }
+        }
+    }
+
+    Err(AutoTagError::MissingField("package version"))
+}
+
+/// Returns whether the value of a Cargo manifest field is
+/// `{ workspace = true }`.
+fn inherits(value: &toml::Value) -> bool {
+    value
+        .get("workspace")
+        .and_then(|workspace| workspace.as_bool())
+        == Some(true)
+}
+
+/// Reads the package tagged for a virtual Cargo workspace with
+/// `--cargo-workspace-tag`.
+///
+/// The name is given by `workspace.metadata.auto-tag.tag-name`, or is the
+/// name of the directory of the workspace. Tagging is enabled like for
+/// packages, with the enable key under `workspace` instead of `package`.
+fn workspace_package(
+    args: &AutoTagArgs,
+    path: &Path,
+    cargo_toml: &toml::Value,
+    workspace: &toml::Value,
+    read: &ReadFile,
+) -> Result<Extracted, AutoTagError> {
+    let version = workspace
+        .get("package")
+        .and_then(|package| package.get("version"));
+
+    let version = match toml_string(version, "workspace package version")? {
+        Some(version) => version.to_string(),
+        None => return Ok(Extracted::Skipped(SkipReason::WorkspaceRoot)),
+    };
+
+    check_semver(args, &version)?;
+
+    let auto_tag = workspace
+        .get("metadata")
+        .and_then(|metadata| metadata.get("auto-tag"));
+
+    // Paths are relative to the root of the repository, also when reading
+    // from trees, so the root itself is named after the repository.
+    let dir: PathBuf = args
+        .repo_dir
+        .join(path.parent().unwrap_or_else(|| Path::new("")))
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    let dir_name = dir.file_name().map(|name| {
+        let name = name.to_string_lossy();
+        name.strip_suffix(".git").unwrap_or(&name).to_string()
+    });
+
+    let name = toml_str_value(auto_tag, "tag-name")
+        .or(dir_name)
+        .ok_or(AutoTagError::MissingField("workspace name"))?;
+
+    let key = enable_key(args, Ecosystem::Cargo, CargoToml.enable_key());
+    let key = workspace_key(key);
+
+    if !allowlisted(args, path, Some(&name), read)?
+        .unwrap_or_else(|| toml_enabled(args, path, cargo_toml, &key))
+    {
+        return Ok(Extracted::Skipped(toml_disabled(args, cargo_toml, &key)));
+    }
+
+    Ok(Extracted::Package(Package {
+        ecosystem: Ecosystem::Cargo,
+        manifest: path.to_path_buf(),
+        package_name: name.clone(),
+        name,
+        version,
+        template: toml_str_value(auto_tag, "template"),
+        prefix: toml_str_value(auto_tag, "prefix"),
+    }))
+}
+
+/// Returns the key that enables tagging for a workspace, e.g.
+/// `workspace.metadata.auto-tag.enabled` for
+/// `package.metadata.auto-tag.enabled`.
+fn workspace_key(key: &str) -> String {
+    match key.strip_prefix("package.") {
+        Some(rest) => format!("workspace.{rest}"),
+        None => key.to_string(),
+    }
+}
+
+/// Returns whether the workspace the Cargo manifest at the path inherits its
+/// version from is tagged with `--cargo-workspace-tag`.
+fn workspace_tagged(
+    args: &AutoTagArgs,
+    path: &Path,
+    cargo_toml: &toml::Value,
+    read: &ReadFile,
+) -> Result<bool, AutoTagError> {
+    let (root_path, root) = workspace_root(args, path, cargo_toml, read)?;
+
+    if root.get("package").is_some() {
+        return Ok(false);
+    }
+
+    let workspace = match root.get("workspace") {
+        Some(workspace) => workspace,
+        None => return Ok(false),
+    };
+
+    let extracted = workspace_package(args, &root_path, &root, workspace, read)?;
+    let extracted = apply_release_file(args, &root_path, read, extracted)?;
+
+    Ok(matches!(extracted, Extracted::Package(_)))
+}
+
+/// Returns the path of the workspace root given by `package.workspace` in the
+/// Cargo manifest at the path, if there is one.
+fn explicit_workspace_root(path: &Path, cargo_toml: &toml::Value) -> Option<PathBuf> {
+    let root = cargo_toml
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(|workspace| workspace.as_str())?;
+
+    // Trees cannot look up `..`, so the path is resolved here.
+    let mut root_dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+    for component in Path::new(root).components() {
+        match component {
+            Component::ParentDir
+                if matches!(
+                    root_dir.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                root_dir.pop();
+            }
+            Component::CurDir => {}
+            component => root_dir.push(component),
+        }
+    }
+
+    Some(root_dir.join("Cargo.toml"))
+}
+
+/// Returns the `workspace.package.version` of the workspace the Cargo
+/// manifest at the path belongs to.
+fn workspace_version(
+    args: &AutoTagArgs,
+    path: &Path,
+    cargo_toml: &toml::Value,
+    read: &ReadFile,
+) -> Result<String, AutoTagError> {
+    let (_, root) = workspace_root(args, path, cargo_toml, read)?;
+
+    let version = root
+        .get("workspace")
+        .and_then(|workspace| workspace.get("package"))
+        .and_then(|package| package.get("version"));
+
+    toml_string(version, "workspace package version")?
+        .map(str::to_string)
+        .ok_or(AutoTagError::MissingField("workspace package version"))
+}
+
+/// Returns the path and the manifest of the workspace the Cargo manifest at
+/// the path belongs to.
+///
+/// The workspace root is given by `package.workspace`, or is the closest
+/// parent directory with a manifest that has a `[workspace]` table.
+fn workspace_root(
+    args: &AutoTagArgs,
+    path: &Path,
+    cargo_toml: &toml::Value,
+    read: &ReadFile,
+) -> Result<(PathBuf, Rc<toml::Value>), AutoTagError> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let root_manifest = match explicit_workspace_root(path, cargo_toml) {
+        Some(root_manifest) => root_manifest,
+        None => match dir.parent() {
+            Some(parent) => args
+                .workspace_cache
+                .cargo_root(parent, read)?
+                .ok_or(AutoTagError::MissingField("workspace root"))?,
+            None => return Err(AutoTagError::MissingField("workspace root")),
+        },
+    };
+
+    match args.workspace_cache.manifest(&root_manifest, read)? {
+        Some(root) => Ok((root_manifest, root)),
+        // Reading it again returns why the root cannot be read.
+        None => Err(read(&root_manifest)
+            .err()
+            .unwrap_or(AutoTagError::MissingField("workspace root"))),
+    }
+}
+
+/// Returns the key that enables tagging in manifests of the ecosystem, as
+/// configured in `[enable-keys]` or the given default.
+pub(crate) fn enable_key<'a>(
+    args: &'a AutoTagArgs,
+    ecosystem: Ecosystem,
+    default: &'a str,
+) -> &'a str {
+    args.config.enable_key(ecosystem).unwrap_or(default)
+}
+
+/// Returns whether the value at the dotted key is `true`, or equals
+/// `--enable-value` if given.
+fn toml_enabled(args: &AutoTagArgs, path: &Path, value: &toml::Value, key: &str) -> bool {
+    match key.split('.').try_fold(value, |value, key| value.get(key)) {
+        Some(value) if args.enable_value.is_some() => {
+            value.as_str() == args.enable_value.as_deref()
+        }
+        Some(toml::Value::Boolean(enabled)) => *enabled,
+        Some(toml::Value::String(enabled)) => enabled_from_str(args, path, key, enabled),
+        _ => false,
+    }
+}
+
+/// Returns whether the value at the dotted key is `true`, or equals
+/// `--enable-value` if given.
+fn json_enabled(args: &AutoTagArgs, path: &Path, value: &serde_json::Value, key: &str) -> bool {
+    match key.split('.').try_fold(value, |value, key| value.get(key)) {
+        Some(value) if args.enable_value.is_some() => {
+            value.as_str() == args.enable_value.as_deref()
+        }
+        Some(serde_json::Value::Bool(enabled)) => *enabled,
+        Some(serde_json::Value::String(enabled)) => enabled_from_str(args, path, key, enabled),
+        _ => false,
+    }
+}
+
+/// Returns whether the package is listed in `--enabled-packages`, or
+/// otherwise whether the [`RELEASE_FILE`] next to the manifest enables it.
+/// Every package is enabled while looking for disabled ones.
+///
+/// Returns `None` if the enable keys of the manifests decide.
+fn allowlisted(
+    args: &AutoTagArgs,
+    path: &Path,
+    name: Option<&str>,
+    read: &ReadFile,
+) -> Result<Option<bool>, AutoTagError> {
+    if args.enable_all.get() {
+        return Ok(Some(true));
+    }
+
+    if args.enabled_packages.is_empty() {
+        return Ok(release_file(args, path, read)?.enabled);
+    }
+
+    Ok(Some(name.is_some_and(|name| {
+        args.enabled_packages.iter().any(|allowed| allowed == name)
+    })))
+}
+
+/// Returns why the manifest is not tagged, depending on whether it has the
+/// key at the dotted path at all.
+fn toml_disabled(args: &AutoTagArgs, value: &toml::Value, key: &str) -> SkipReason {
+    if !args.enabled_packages.is_empty() {
+        return SkipReason::Disabled;
+    }
+
+    match key.split('.').try_fold(value, |value, key| value.get(key)) {
+        Some(_) => SkipReason::Disabled,
+        None => SkipReason::Unconfigured,
+    }
+}
+
+/// Returns why the manifest is not tagged, depending on whether it has the
+/// key at the dotted path at all.
+fn json_disabled(args: &AutoTagArgs, value: &serde_json::Value, key: &str) -> SkipReason {
+    if !args.enabled_packages.is_empty() {
+        return SkipReason::Disabled;
+    }
+
+    match key.split('.').try_fold(value, |value, key| value.get(key)) {
+        Some(_) => SkipReason::Disabled,
+        None => SkipReason::Unconfigured,
+    }
+}
+
+/// Accepts `"true"`, `"false"`, `"1"` and `"0"` as the enable flag, warning
+/// that it should be a boolean.
+fn enabled_from_str(args: &AutoTagArgs, path: &Path, key: &str, enabled: &str) -> bool {
+    let enabled = match enabled {
+        "true" | "1" => true,
+        "false" | "0" => false,
+        _ => return false,
+    };
+
+    args.print(format!(
+        "warning: {key} in {} is a string, use {enabled} instead",
+        log_path(path)
+    ));
+
+    enabled
+}
+
+fn file_name_is(path: &Path, names: &[&str]) -> bool {
+    path.file_name()
+        .map(|f| names.iter().any(|name| f == *name))
+        .unwrap_or(false)
+}
+
+struct CargoToml;
+
+impl PackageSource for CargoToml {
+    fn enable(&self, contents: &str) -> Result<Option<String>, anyhow::Error> {
+        enable_toml(contents, &["package"], &["package", "metadata", "auto-tag"])
+    }
+
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Cargo
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        vec!["Cargo.toml".to_string()]
+    }
+
+    fn enable_key(&self) -> &str {
+        "package.metadata.auto-tag.enabled"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        file_name_is(path, &["Cargo.toml"])
+    }
+
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        toml_str: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError> {
+        let cargo_toml: toml::Value = toml::from_str(toml_str)?;
+
+        let auto_tag = cargo_toml
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("auto-tag"));
+
+        // Manifests of scripts define targets without a package.
+        if cargo_toml.get("package").is_none() && cargo_toml.get("workspace").is_none() {
+            return Ok(Extracted::Skipped(SkipReason::NoPackage));
+        }
+
+        let key = enable_key(args, Ecosystem::Cargo, self.enable_key());
+
+        let name = cargo_toml
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str());
+
+        // Virtual workspace roots are enabled separately below.
+        if cargo_toml.get("package").is_some()
+            && allowlisted(args, path, name, read)?
+                .unwrap_or_else(|| toml_enabled(args, path, &cargo_toml, key))
+        {
+            let name = cargo_toml
+                .get("package")
+                .and_then(|package| package.get("name"));
+            let name = toml_string(name, "package name")?
+                .ok_or(AutoTagError::MissingField("package name"))?;
+
+            let locked = if args.cargo_version_from_lock {
+                locked_version(path, name, read)?
+            } else {
+                None
+            };
+
+            let version = match cargo_toml
+                .get("package")
+                .and_then(|package| package.get("version"))
+            {
+                Some(toml::Value::String(version)) => locked.unwrap_or_else(|| version.clone()),
+                Some(version)
+                    if inherits(version)
+                        && args.cargo_workspace_tag
+                        && workspace_tagged(args, path, &cargo_toml, read)? =>
+                {
+                    return Ok(Extracted::Skipped(SkipReason::WorkspaceMember));
+                }
+                Some(version) if inherits(version) => match locked {
+                    Some(locked) => locked,
+                    None => workspace_version(args, path, &cargo_toml, read)?,
+                },
+                Some(version) => {
+                    return Err(AutoTagError::InvalidType(
+                        "package version",
+                        version.type_str(),
+                    ))
+                }
+                None => match locked {
+                    Some(locked) => locked,
+                    None => version_or_fallback(args, path, None, read)?,
+                },
+            };
+
+            check_semver(args, &version)?;
+
+            let targets = target_names(&cargo_toml, name);
+
+            if !targets.is_empty() {
+                args.print_progress(format!(
+                    "{} is tagged as {name:?}, not as its targets {}",
+                    log_path(path),
+                    targets.join(", ")
+                ));
+            }
+
+            return Ok(Extracted::Package(Package {
+                ecosystem: Ecosystem::Cargo,
+                manifest: path.to_path_buf(),
+                package_name: name.to_string(),
+                name: toml_str_value(auto_tag, "tag-name").unwrap_or_else(|| name.to_string()),
+                version,
+                template: toml_str_value(auto_tag, "template"),
+                prefix: toml_str_value(auto_tag, "prefix"),
+            }));
+        }
+
+        if cargo_toml.get("package").is_none() {
+            if let Some(workspace) = cargo_toml.get("workspace") {
+                if args.cargo_workspace_tag {
+                    return workspace_package(args, path, &cargo_toml, workspace, read);
+                }
+
+                return Ok(Extracted::Skipped(SkipReason::WorkspaceRoot));
+            }
+        }
+
+        Ok(Extracted::Skipped(toml_disabled(args, &cargo_toml, key)))
+    }
+}
+
+/// Returns the version of the package recorded in the `Cargo.lock` next to
+/// the manifest, if there is one with an entry for it.
+///
+/// Only entries without a `source` are considered, as those of dependencies
+/// with the same name come from a registry or git.
+fn locked_version(
+    path: &Path,
+    name: &str,
+    read: &ReadFile,
+) -> Result<Option<String>, AutoTagError> {
+    let contents = match read(&path.with_file_name("Cargo.lock")) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+    let cargo_lock: toml::Value = toml::from_str(&contents)?;
+
+    let version = cargo_lock
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|package| package.get("source").is_none())
+        .find(|package| package.get("name").and_then(|name| name.as_str()) == Some(name))
+        .and_then(|package| package.get("version"))
+        .and_then(|version| version.as_str())
+        .map(str::to_string);
+
+    Ok(version)
+}
+
+/// Returns the names of the `[lib]` and `[[bin]]` targets that differ from
+/// the package name.
+///
+/// Library names with `_` instead of `-` are the same as the package name.
+fn target_names<'v>(cargo_toml: &'v toml::Value, name: &str) -> Vec<&'v str> {
+    let lib = cargo_toml.get("lib").into_iter();
+    let bins = cargo_toml
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .into_iter()
+        .flatten();
+
+    lib.chain(bins)
+        .filter_map(|target| target.get("name"))
+        .filter_map(|target| target.as_str())
+        .filter(|target| target.replace('_', "-") != name.replace('_', "-"))
+        .collect()
+}
+
+struct PackageJson;
+
+impl PackageSource for PackageJson {
+    fn enable(&self, contents: &str) -> Result<Option<String>, anyhow::Error> {
+        enable_json(contents)
+    }
+
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Npm
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        vec!["package.json".to_string()]
+    }
+
+    fn enable_key(&self) -> &str {
+        "autoTag.enabled"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        file_name_is(path, &["package.json"])
+    }
+
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        json_str: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError> {
+        let enable_key = enable_key(args, Ecosystem::Npm, self.enable_key());
+        let enable_field = enable_key.split('.').next().unwrap_or_default();
+
+        let package_json = parse_package_json(
+            args,
+            json_str,
+            &["name", "version", "autoTag", enable_field],
+        )?;
+
+        package_from_json(args, Ecosystem::Npm, path, &package_json, read)
+    }
+}
+
+/// Parses a `package.json`, or with `--allow-jsonc` a JSONC file.
+///
+/// Only the given top-level fields are parsed from JSON, as generated
+/// manifests can be huge.
+fn parse_package_json(
+    args: &AutoTagArgs,
+    json_str: &str,
+    fields: &[&str],
+) -> Result<serde_json::Value, AutoTagError> {
+    if args.allow_jsonc {
+        return Ok(json5::from_str(json_str)?);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(json_str);
+    let fields = TopLevelFields(fields).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok(serde_json::Value::Object(fields))
+}
+
+/// Deserializes the given fields of a JSON object, skipping the values of
+/// all other fields without building them.
+struct TopLevelFields<'f>(&'f [&'f str]);
+
+impl<'de> DeserializeSeed<'de> for TopLevelFields<'_> {
+    type Value = serde_json::Map<String, serde_json::Value>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TopLevelFields<'_> {
+    type Value = serde_json::Map<String, serde_json::Value>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = serde_json::Map::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            if self.0.contains(&key.as_str()) {
+                fields.insert(key, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(fields)
+    }
+}
+
+struct DenoJson;
+
+impl PackageSource for DenoJson {
+    fn enable(&self, contents: &str) -> Result<Option<String>, anyhow::Error> {
+        enable_json(contents)
+    }
+
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Deno
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        vec!["deno.json".to_string(), "deno.jsonc".to_string()]
+    }
+
+    fn enable_key(&self) -> &str {
+        "autoTag.enabled"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        file_name_is(path, &["deno.json", "deno.jsonc"])
+    }
+
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        json_str: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError> {
+        // Deno accepts comments and trailing commas in both `deno.json` and `deno.jsonc`.
+        let deno_json: serde_json::Value = json5::from_str(json_str)?;
+
+        package_from_json(args, Ecosystem::Deno, path, &deno_json, read)
+    }
+}
+
+/// Reads a package from a `package.json`-like manifest.
+fn package_from_json(
+    args: &AutoTagArgs,
+    ecosystem: Ecosystem,
+    path: &Path,
+    package_json: &serde_json::Value,
+    read: &ReadFile,
+) -> Result<Extracted, AutoTagError> {
+    let auto_tag = &package_json["autoTag"];
+
+    let key = enable_key(args, ecosystem, "autoTag.enabled");
+
+    if allowlisted(args, path, package_json["name"].as_str(), read)?
+        .unwrap_or_else(|| json_enabled(args, path, package_json, key))
+    {
+        let package_name = json_string(&package_json["name"], "package name")?
+            .ok_or(AutoTagError::MissingField("package name"))?;
+
+        let name = match auto_tag["tagName"].as_str() {
+            Some(tag_name) => tag_name.to_string(),
+            None => npm_tag_name(args, package_name),
+        };
+
+        let version = version_or_fallback(
+            args,
+            path,
+            json_string(&package_json["version"], "package version")?,
+            read,
+        )?;
+
+        let version = match &args.npm_version_from_dist_tag {
+            Some(dist_tag) if ecosystem == Ecosystem::Npm => {
+                match dist_tag_version(args, package_name, dist_tag) {
+                    Ok(version) => version,
+                    Err(err) => {
+                        args.print(format!(
+                            r#"warning: cannot read the "{dist_tag}" dist-tag of {package_name:?}, using the version in {}: {err}"#,
+                            log_path(path)
+                        ));
+                        version
+                    }
+                }
+            }
+            _ => version,
+        };
+
+        check_semver(args, &version)?;
+
+        return Ok(Extracted::Package(Package {
+            ecosystem,
+            manifest: path.to_path_buf(),
+            package_name: package_name.to_string(),
+            name,
+            version,
+            template: auto_tag["template"].as_str().map(str::to_string),
+            prefix: auto_tag["prefix"].as_str().map(str::to_string),
+        }));
+    }
+
+    Ok(Extracted::Skipped(json_disabled(args, package_json, key)))
+}
+
+/// Returns the version the dist-tag of the package points to in the npm
+/// registry, as reported by `npm view`.
+///
+/// Gives up after `--network-timeout`.
+fn dist_tag_version(
+    args: &AutoTagArgs,
+    package_name: &str,
+    dist_tag: &str,
+) -> Result<String, String> {
+    let mut child = Command::new("npm")
+        .args(["view", &format!("{package_name}@{dist_tag}"), "version"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("failed to run npm: {err}"))?;
+
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let started = Instant::now();
+
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|err| format!("failed to run npm: {err}"))?
+        {
+            break status;
+        }
+
+        if args.network_timeout > 0 && started.elapsed().as_secs() >= args.network_timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("timed out after {}s", args.network_timeout));
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(format!("npm exited with {status}: {}", stderr.trim()));
+    }
+
+    let version = String::from_utf8_lossy(&stdout).trim().to_string();
+
+    if version.is_empty() {
+        return Err("the dist-tag does not exist".to_string());
+    }
+
+    Ok(version)
+}
+
+/// Reads the pipe of a child process to the end on another thread, so that
+/// the child never blocks on a full pipe.
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+/// Turns a possibly scoped npm or JSR package name into a name usable in tags.
+fn npm_tag_name(args: &AutoTagArgs, package_name: &str) -> String {
+    if args.strip_scope {
+        if let Some((_, name)) = package_name
+            .strip_prefix('@')
+            .and_then(|name| name.split_once('/'))
+        {
+            return name.to_string();
+        }
+    }
+
+    package_name
+        .replace('@', &args.scope_prefix)
+        .replace('/', &args.scope_separator)
+}
+
+struct PyprojectToml;
+
+impl PackageSource for PyprojectToml {
+    fn enable(&self, contents: &str) -> Result<Option<String>, anyhow::Error> {
+        enable_toml(contents, &["tool.poetry", "project"], &["tool", "auto-tag"])
+    }
+
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Python
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        vec!["pyproject.toml".to_string()]
+    }
+
+    fn enable_key(&self) -> &str {
+        "tool.auto-tag.enabled"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        file_name_is(path, &["pyproject.toml"])
+    }
+
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        toml_str: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError> {
+        let pyproject_toml: toml::Value = toml::from_str(toml_str)?;
+
+        let auto_tag = pyproject_toml
+            .get("tool")
+            .and_then(|package| package.get("auto-tag"));
+
+        let key = enable_key(args, Ecosystem::Python, self.enable_key());
+
+        let poetry = pyproject_toml
+            .get("tool")
+            .and_then(|tool| tool.get("poetry"));
+        let project = pyproject_toml.get("project");
+
+        if poetry.or(project).is_none() && uv_workspace(&pyproject_toml).is_some() {
+            return Ok(Extracted::Skipped(SkipReason::WorkspaceRoot));
+        }
+
+        let name = poetry
+            .or(project)
+            .and_then(|table| table.get("name"))
+            .and_then(|name| name.as_str());
+
+        // Members of uv workspaces without their own enable key inherit the
+        // one of the workspace root.
+        let has_key = key
+            .split('.')
+            .try_fold(&pyproject_toml, |value, key| value.get(key))
+            .is_some();
+        let root = if has_key {
+            None
+        } else {
+            uv_workspace_root(args, path, read)?
+        };
+        let (config_path, config) = root
+            .as_ref()
+            .map_or((path, &pyproject_toml), |(root_path, root)| {
+                (root_path.as_path(), root)
+            });
+
+        if allowlisted(args, path, name, read)?
+            .unwrap_or_else(|| toml_enabled(args, config_path, config, key))
+        {
+            let name = poetry.or(project).and_then(|table| table.get("name"));
+            let name = toml_string(name, "package name")?
+                .ok_or(AutoTagError::MissingField("package name"))?;
+            let tag_name = toml_str_value(auto_tag, "tag-name").unwrap_or_else(|| {
+                if args.no_python_normalize_names {
+                    name.to_string()
+                } else {
+                    normalize_python_name(name)
+                }
+            });
+
+            let version = poetry.or(project).and_then(|table| table.get("version"));
+            let version = toml_string(version, "package version")?.map(str::to_string);
+
+            let version = match version {
+                Some(version) => version,
+                None if is_dynamic_version(project) => {
+                    if args.scm_version && version_source(&pyproject_toml) == (None, None) {
+                        return Ok(Extracted::ScmVersion(Package {
+                            ecosystem: Ecosystem::Python,
+                            manifest: path.to_path_buf(),
+                            package_name: name.to_string(),
+                            name: tag_name,
+                            version: String::new(),
+                            template: toml_str_value(auto_tag, "template"),
+                            prefix: toml_str_value(auto_tag, "prefix"),
+                        }));
+                    }
+
+                    match dynamic_version(&pyproject_toml, path, read) {
+                        Ok(version) => version,
+                        Err(err) => match version_or_fallback(args, path, None, read) {
+                            Ok(version) => version,
+                            Err(_) => {
+                                args.print_progress(format!(
+                                    "cannot resolve the dynamic version of {}: {err}",
+                                    log_path(path)
+                                ));
+                                return Ok(Extracted::Skipped(SkipReason::UnresolvedVersion));
+                            }
+                        },
+                    }
+                }
+                None => version_or_fallback(args, path, None, read)?,
+            };
+
+            return Ok(Extracted::Package(Package {
+                ecosystem: Ecosystem::Python,
+                manifest: path.to_path_buf(),
+                package_name: name.to_string(),
+                name: tag_name,
+                version,
+                template: toml_str_value(auto_tag, "template"),
+                prefix: toml_str_value(auto_tag, "prefix"),
+            }));
+        }
+
+        Ok(Extracted::Skipped(toml_disabled(args, config, key)))
+    }
+}
+
+/// Returns the `[tool.uv.workspace]` table of the pyproject manifest.
+fn uv_workspace(pyproject_toml: &toml::Value) -> Option<&toml::Value> {
+    pyproject_toml
+        .get("tool")
+        .and_then(|tool| tool.get("uv"))
+        .and_then(|uv| uv.get("workspace"))
+}
+
+/// Returns whether the directory, relative to the root of the uv workspace
+/// declared in the pyproject manifest, is one of its `members` and not
+/// `exclude`d.
+///
+/// Invalid globs are ignored, uv itself rejects them.
+pub(crate) fn is_uv_member(pyproject_toml: &toml::Value, dir: &Path) -> bool {
+    let workspace = match uv_workspace(pyproject_toml) {
+        Some(workspace) => workspace,
+        None => return false,
+    };
+
+    let matches = |key: &str| {
+        workspace
+            .get(key)
+            .and_then(|value| value.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str())
+            .filter_map(|pattern| {
+                let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+                GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .ok()
+            })
+            .any(|glob| glob.compile_matcher().is_match(dir))
+    };
+
+    matches("members") && !matches("exclude")
+}
+
+/// Returns the path and the manifest of the uv workspace the pyproject
+/// manifest at the path is a member of.
+///
+/// The workspace root is the closest parent directory with a
+/// `pyproject.toml` that lists the manifest's directory as a member.
+fn uv_workspace_root(
+    args: &AutoTagArgs,
+    path: &Path,
+    read: &ReadFile,
+) -> Result<Option<(PathBuf, Rc<toml::Value>)>, AutoTagError> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    for ancestor in dir.ancestors().skip(1) {
+        let root_path = ancestor.join("pyproject.toml");
+
+        let candidate = match args.workspace_cache.manifest(&root_path, read)? {
+            Some(candidate) => candidate,
+            None => continue,
+        };
+
+        let member_dir = match dir.strip_prefix(ancestor) {
+            Ok(member_dir) => member_dir,
+            Err(_) => continue,
+        };
+
+        if is_uv_member(&candidate, member_dir) {
+            return Ok(Some((root_path, candidate)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Normalizes a Python package name as described in PEP 503, e.g.
+/// `My_Package` to `my-package`.
+fn normalize_python_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+
+    normalized
+}
+
+/// Returns whether the `[project]` table lists the version as dynamic.
+fn is_dynamic_version(project: Option<&toml::Value>) -> bool {
+    project
+        .and_then(|project| project.get("dynamic"))
+        .and_then(|dynamic| dynamic.as_array())
+        .is_some_and(|dynamic| {
+            dynamic
+                .iter()
+                .any(|field| field.as_str() == Some("version"))
+        })
+}
+
+/// Returns the setuptools `attr` and the hatch `path` a dynamic version is
+/// read from.
+fn version_source(pyproject_toml: &toml::Value) -> (Option<&str>, Option<&str>) {
+    let tool = pyproject_toml.get("tool");
+
+    let setuptools_attr = tool
+        .and_then(|tool| tool.get("setuptools"))
+        .and_then(|setuptools| setuptools.get("dynamic"))
+        .and_then(|dynamic| dynamic.get("version"))
+        .and_then(|version| version.get("attr"))
+        .and_then(|attr| attr.as_str());
+
+    let hatch_path = tool
+        .and_then(|tool| tool.get("hatch"))
+        .and_then(|hatch| hatch.get("version"))
+        .and_then(|version| version.get("path"))
+        .and_then(|path| path.as_str());
+
+    (setuptools_attr, hatch_path)
+}
+
+/// Resolves a dynamic version from the Python file given by the setuptools
+/// `attr` or the hatch `path`.
+fn dynamic_version(
+    pyproject_toml: &toml::Value,
+    path: &Path,
+    read: &ReadFile,
+) -> Result<String, String> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let (files, attributes): (Vec<PathBuf>, Vec<&str>) = match version_source(pyproject_toml) {
+        (Some(attr), _) => {
+            let (module, attribute) = attr
+                .rsplit_once('.')
+                .ok_or_else(|| format!("invalid setuptools attr {attr:?}"))?;
+            let module = module.replace('.', "/");
+
+            // Both flat and `src` layouts, and modules as well as packages.
+            let files = ["", "src/"]
+                .iter()
+                .flat_map(|root| {
+                    [
+                        dir.join(format!("{root}{module}/__init__.py")),
+                        dir.join(format!("{root}{module}.py")),
+                    ]
+                })
+                .collect();
+
+            (files, vec![attribute])
+        }
+        (None, Some(file)) => (vec![dir.join(file)], vec!["__version__", "VERSION"]),
+        (None, None) => {
+            return Err("neither tool.setuptools.dynamic.version.attr nor \
+                        tool.hatch.version.path is set"
+                .into())
+        }
+    };
+
+    let (file, contents) = files
+        .iter()
+        .find_map(|file| read(file).ok().map(|contents| (file, contents)))
+        .ok_or_else(|| format!("cannot read {}", log_path(&files[0])))?;
+
+    contents
+        .lines()
+        .find_map(|line| {
+            attributes
+                .iter()
+                .find_map(|attribute| python_string_assignment(line, attribute))
+        })
+        .ok_or_else(|| {
+            format!(
+                "no {} string found in {}",
+                attributes.join(" or "),
+                log_path(file)
+            )
+        })
+}
+
+/// Returns the string literal assigned to the attribute in a line of Python,
+/// such as `__version__ = "1.0.0"` or `__version__: str = '1.0.0'`.
+fn python_string_assignment(line: &str, attribute: &str) -> Option<String> {
+    let rest = line.strip_prefix(attribute)?.trim_start();
+
+    let rest = match rest.strip_prefix(':') {
+        Some(annotated) => annotated.split_once('=')?.1,
+        None => rest.strip_prefix('=')?,
+    }
+    .trim();
+
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let literal = &rest[1..];
+    let end = literal.find(quote)?;
+
+    Some(literal[..end].to_string())
+}
+
+/// An external command that detects packages in files matching a glob.
+///
+/// The command is invoked with the path of the file as its only argument
+/// and the contents of the file on its standard input, and is expected to print `{"name": ..., "version": ..., "enabled": ...}`
+/// as JSON.
+pub(crate) struct Detector {
+    command: PathBuf,
+    glob: GlobMatcher,
+    /// Whether the glob only matches file names.
+    file_name_only: bool,
+}
+
+impl Detector {
+    pub(crate) fn new(command: &Path, glob: &str) -> Result<Self, anyhow::Error> {
+        Ok(Detector {
+            command: command.to_path_buf(),
+            glob: Glob::new(glob)
+                .map_err(|err| UsageError(format!("invalid --detector-glob: {}", err)))?
+                .compile_matcher(),
+            file_name_only: !glob.contains('/'),
+        })
+    }
+
+    /// Returns the detector given on the command line, if any.
+    pub(crate) fn from_args(args: &AutoTagArgs) -> Result<Option<Self>, anyhow::Error> {
+        match (&args.detector, &args.detector_glob) {
+            (Some(command), Some(glob)) => Ok(Some(Detector::new(command, glob)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct DetectedPackage {
+    name: String,
+    version: String,
+    enabled: bool,
+}
+
+impl PackageSource for Detector {
+    fn enable(&self, _contents: &str) -> Result<Option<String>, anyhow::Error> {
+        Err(anyhow!(
+            "tagging cannot be enabled for files handled by {:?}",
+            self.command
+        ))
+    }
+
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::External
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        vec![self.glob.glob().to_string()]
+    }
+
+    fn enable_key(&self) -> &str {
+        "enabled"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        if self.file_name_only {
+            return path
+                .file_name()
+                .map(|name| self.glob.is_match(name))
+                .unwrap_or(false);
+        }
+
+        self.glob.is_match(path.strip_prefix(".").unwrap_or(path))
+    }
+
+    fn extract(
+        &self,
+        args: &AutoTagArgs,
+        path: &Path,
+        contents: &str,
+        read: &ReadFile,
+    ) -> Result<Extracted, AutoTagError> {
+        let mut child = Command::new(&self.command)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| {
+                AutoTagError::Detector(format!("failed to run {:?}: {}", self.command, err))
+            })?;
+
+        let stdin = child.stdin.take();
+
+        let output = std::thread::scope(|scope| {
+            // The input is written from another thread, so that a command
+            // printing its output before reading all of it does not block.
+            // The command is free to ignore its input and read the file instead.
+            if let Some(mut stdin) = stdin {
+                scope.spawn(move || {
+                    let _ = stdin.write_all(contents.as_bytes());
+                });
+            }
+
+            child.wait_with_output()
+        })
+        .map_err(|err| {
+            AutoTagError::Detector(format!("failed to run {:?}: {}", self.command, err))
+        })?;
+
+        if !output.status.success() {
+            return Err(AutoTagError::Detector(format!(
+                "{:?} failed with {}",
+                self.command, output.status
+            )));
+        }
+
+        let detected: DetectedPackage = serde_json::from_slice(&output.stdout).map_err(|err| {
+            AutoTagError::Detector(format!("invalid output from {:?}: {}", self.command, err))
+        })?;
+
+        if !allowlisted(args, path, Some(&detected.name), read)?.unwrap_or(detected.enabled) {
+            return Ok(Extracted::Skipped(SkipReason::Disabled));
+        }
+
+        Ok(Extracted::Package(Package {
+            ecosystem: Ecosystem::External,
+            manifest: path.to_path_buf(),
+            package_name: detected.name.clone(),
+            name: detected.name,
+            version: detected.version,
+            template: None,
+            prefix: None,
+        }))
+    }
+}
+
+/// Sets `enabled = true` in the table at `keys`, keeping the rest of the
+/// document as is.
+///
+/// One of the tables at the dotted `packages` paths must exist.
+fn enable_toml(
+    contents: &str,
+    packages: &[&str],
+    keys: &[&str],
+) -> Result<Option<String>, anyhow::Error> {
+    let mut document: toml_edit::Document = contents.parse()?;
+
+    let has_package = packages.iter().any(|package| {
+        package
+            .split('.')
+            .try_fold(document.as_item(), |item, key| item.get(key))
+            .is_some_and(|item| item.is_table_like())
+    });
+
+    if !has_package {
+        let tables: Vec<String> = packages
+            .iter()
+            .map(|package| format!("[{package}]"))
+            .collect();
+        return Err(anyhow!("{} not found", tables.join(" or ")));
+    }
+
+    let mut table = document.as_table_mut();
+    for (i, key) in keys.iter().enumerate() {
+        table = table
+            .entry(key)
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                // Only the innermost table gets a header.
+                table.set_implicit(i + 1 < keys.len());
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("{} is not a table", keys[..=i].join(".")))?;
+    }
+
+    if table.get("enabled").and_then(|enabled| enabled.as_bool()) == Some(true) {
+        return Ok(None);
+    }
+
+    table["enabled"] = toml_edit::value(true);
+
+    let mut edited = document.to_string();
+
+    // toml_edit always writes LF line endings.
+    if contents.contains("\r\n") {
+        edited = edited.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+
+    Ok(Some(edited))
+}
+
+/// Adds `"autoTag": {"enabled": true}` to a `package.json`-like manifest.
+///
+/// The key is inserted as text at the beginning of the object to keep the
+/// formatting of the rest of the file.
+fn enable_json(contents: &str) -> Result<Option<String>, anyhow::Error> {
+    let manifest: serde_json::Value = json5::from_str(contents)?;
+
+    if manifest["name"].as_str().is_none() {
+        return Err(anyhow!("package name not found"));
+    }
+
+    match &manifest["autoTag"] {
+        serde_json::Value::Null => {}
+        auto_tag if auto_tag["enabled"].as_bool() == Some(true) => return Ok(None),
+        _ => {
+            return Err(anyhow!(
+                r#""autoTag" already exists, set "enabled" manually"#
+            ))
+        }
+    }
+
+    let start = contents
+        .find('{')
+        .ok_or_else(|| anyhow!("expected an object"))?;
+
+    let (head, tail) = contents.split_at(start + 1);
+
+    if !tail.trim_start_matches([' ', '\t', '\r']).starts_with('\n') {
+        return Ok(Some(format!(
+            r#"{head}"autoTag":{{"enabled":true}},{tail}"#
+        )));
+    }
+
+    // Use the indentation of the first line within the object.
+    let indent: String = tail
+        .lines()
+        .nth(1)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let indent = if indent.is_empty() { "  " } else { &indent };
+    let newline = if contents.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    Ok(Some(format!(
+        "{head}{newline}{indent}\"autoTag\": {{{newline}{indent}{indent}\"enabled\": true{newline}{indent}}},{tail}"
+    )))
+}
+
+/// Checks that the version is valid semver with `--strict-versions`, as
+/// required by Cargo, npm and JSR.
+fn check_semver(args: &AutoTagArgs, version: &str) -> Result<(), AutoTagError> {
+    if !args.strict_versions {
+        return Ok(());
+    }
+
+    semver::Version::parse(version)
+        .map(|_| ())
+        .map_err(|err| AutoTagError::InvalidVersion(version.to_string(), err))
+}
+
+/// Returns the string value of a TOML manifest field, failing if the field
+/// has another type.
+fn toml_string<'v>(
+    value: Option<&'v toml::Value>,
+    field: &'static str,
+) -> Result<Option<&'v str>, AutoTagError> {
+    match value {
+        None => Ok(None),
+        Some(toml::Value::String(value)) => Ok(Some(value)),
+        Some(value) => Err(AutoTagError::InvalidType(field, value.type_str())),
+    }
+}
+
+/// Returns the string value of a JSON manifest field, failing if the field
+/// has another type.
+fn json_string<'v>(
+    value: &'v serde_json::Value,
+    field: &'static str,
+) -> Result<Option<&'v str>, AutoTagError> {
+    let found = match value {
+        serde_json::Value::Null => return Ok(None),
+        serde_json::Value::String(value) => return Ok(Some(value)),
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    };
+
+    Err(AutoTagError::InvalidType(field, found))
+}
+
+fn toml_str_value(table: Option<&toml::Value>, key: &str) -> Option<String> {
+    table
+        .and_then(|table| table.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use clap::Parser;
+
+    pub(crate) fn args(extra: &[&str]) -> AutoTagArgs {
+        let mut all = vec!["auto-tag", "--git-user-name", "a", "--git-user-email", "b"];
+        all.extend_from_slice(extra);
+        AutoTagArgs::parse_from(all)
+    }
+
+    /// Returns `read` for the given files, as if they were read from a tree.
+    pub(crate) fn files<'f>(
+        files: &'f [(&'f str, &'f str)],
+    ) -> impl Fn(&Path) -> Result<String, AutoTagError> + 'f {
+        move |path: &Path| {
+            files
+                .iter()
+                .find(|(name, _)| Path::new(name) == path)
+                .map(|(_, contents)| contents.to_string())
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound).into())
+        }
+    }
+
+    /// Extracts the first of the files with the source.
+    pub(crate) fn extract(
+        source: &dyn PackageSource,
+        args: &AutoTagArgs,
+        manifests: &[(&str, &str)],
+    ) -> Result<Extracted, AutoTagError> {
+        let (path, contents) = manifests[0];
+        source.extract(args, Path::new(path), contents, &files(manifests))
+    }
+
+    pub(crate) fn package(extracted: Result<Extracted, AutoTagError>) -> Package {
+        match extracted {
+            Ok(Extracted::Package(package)) => package,
+            Ok(Extracted::ScmVersion(_)) => panic!("expected a package, got an scm version"),
+            Ok(Extracted::Skipped(reason)) => {
+                panic!("expected a package, skipped as {}", reason.name())
+            }
+            Err(err) => panic!("expected a package, got {err}"),
+        }
+    }
+
+    pub(crate) fn skipped(extracted: Result<Extracted, AutoTagError>) -> &'static str {
+        match extracted {
+            Ok(Extracted::Skipped(reason)) => reason.name(),
+            Ok(_) => panic!("expected the manifest to be skipped"),
+            Err(err) => panic!("expected the manifest to be skipped, got {err}"),
+        }
+    }
+
+    #[test]
+    fn cargo_toml() {
+        assert!(CargoToml.matches(Path::new("crates/foo/Cargo.toml")));
+        assert!(!CargoToml.matches(Path::new("crates/foo/Cargo.lock")));
+
+        let package = package(extract(
+            &CargoToml,
+            &args(&[]),
+            &[(
+                "foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion = \"1.2.3\"\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n",
+            )],
+        ));
+
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.version, "1.2.3");
+
+        let reason = skipped(extract(
+            &CargoToml,
+            &args(&[]),
+            &[(
+                "foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion = \"1.2.3\"\n",
+            )],
+        ));
+
+        assert_eq!(reason, "unconfigured");
+    }
+
+    /// Writes the files into a temporary directory.
+    fn temp_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        dir
+    }
+
+    /// Extracts the Cargo manifest at the path within the directory.
+    fn extract_cargo(dir: &Path, manifest: &str) -> Result<Extracted, AutoTagError> {
+        let path = dir.join(manifest);
+        let read = |path: &Path| Ok(std::fs::read_to_string(path)?);
+        let contents = read(&path)?;
+
+        CargoToml.extract(&args(&[]), &path, &contents, &read)
+    }
+
+    const WORKSPACE: &str = "[workspace]\nmembers = [\"crates/*\"]\n\n\
+                             [workspace.package]\nversion = \"1.2.3\"\n";
+
+    #[test]
+    fn cargo_toml_inherited_version_dotted_key() {
+        let dir = temp_dir(&[
+            ("Cargo.toml", WORKSPACE),
+            (
+                "crates/foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion.workspace = true\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n",
+            ),
+        ]);
+
+        let package = package(extract_cargo(dir.path(), "crates/foo/Cargo.toml"));
+        assert_eq!(package.version, "1.2.3");
+    }
+
+    #[test]
+    fn cargo_toml_inherited_version_inline_table() {
+        let dir = temp_dir(&[
+            ("Cargo.toml", WORKSPACE),
+            (
+                "crates/foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion = { workspace = true }\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n",
+            ),
+        ]);
+
+        let package = package(extract_cargo(dir.path(), "crates/foo/Cargo.toml"));
+        assert_eq!(package.version, "1.2.3");
+    }
+
+    #[test]
+    fn cargo_toml_inherited_version_explicit_workspace() {
+        // The closest workspace is not the one the package belongs to.
+        let dir = temp_dir(&[
+            ("Cargo.toml", WORKSPACE),
+            (
+                "other/Cargo.toml",
+                "[workspace]\nmembers = [\"../crates/foo\"]\n\n\
+                 [workspace.package]\nversion = \"2.0.0\"\n",
+            ),
+            (
+                "crates/foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion.workspace = true\n\
+                 workspace = \"../../other\"\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n",
+            ),
+        ]);
+
+        let package = package(extract_cargo(dir.path(), "crates/foo/Cargo.toml"));
+        assert_eq!(package.version, "2.0.0");
+    }
+
+    #[test]
+    fn cargo_toml_inherited_version_missing() {
+        let dir = temp_dir(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+            (
+                "crates/foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion.workspace = true\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n",
+            ),
+        ]);
+
+        match extract_cargo(dir.path(), "crates/foo/Cargo.toml") {
+            Err(err) => assert_eq!(err.to_string(), "workspace package version not found"),
+            Ok(_) => panic!("expected the missing workspace version to fail"),
+        }
+    }
+
+    #[test]
+    fn cargo_toml_without_package() {
+        let reason = skipped(extract(
+            &CargoToml,
+            &args(&[]),
+            &[(
+                "scripts/Cargo.toml",
+                "[[bin]]\nname = \"script\"\npath = \"script.rs\"\n",
+            )],
+        ));
+
+        assert_eq!(reason, "no-package");
+    }
+
+    #[test]
+    fn cargo_toml_target_names() {
+        let cargo_toml: toml::Value =
+            toml::from_str("[package]\nname = \"foo-bar\"\n\n[lib]\nname = \"foo_core\"\n")
+                .unwrap();
+        assert_eq!(target_names(&cargo_toml, "foo-bar"), ["foo_core"]);
+
+        // `_` in library names is the same as `-` in the package name.
+        let cargo_toml: toml::Value =
+            toml::from_str("[package]\nname = \"foo-bar\"\n\n[lib]\nname = \"foo_bar\"\n").unwrap();
+        assert!(target_names(&cargo_toml, "foo-bar").is_empty());
+    }
+
+    #[test]
+    fn cargo_toml_multiple_bins() {
+        let cargo_toml: toml::Value = toml::from_str(
+            "[package]\nname = \"tools\"\n\n\
+             [[bin]]\nname = \"tools\"\n\n\
+             [[bin]]\nname = \"tool-a\"\n\n\
+             [[bin]]\nname = \"tool-b\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(target_names(&cargo_toml, "tools"), ["tool-a", "tool-b"]);
+
+        let package = package(extract(
+            &CargoToml,
+            &args(&[]),
+            &[(
+                "tools/Cargo.toml",
+                "[package]\nname = \"tools\"\nversion = \"0.1.0\"\n\n\
+                 [package.metadata.auto-tag]\nenabled = true\n\n\
+                 [[bin]]\nname = \"tool-a\"\n\n[[bin]]\nname = \"tool-b\"\n",
+            )],
+        ));
+
+        assert_eq!(package.name, "tools");
+    }
+
+    #[test]
+    fn package_json() {
+        assert!(PackageJson.matches(Path::new("web/package.json")));
+        assert!(!PackageJson.matches(Path::new("web/package-lock.json")));
+
+        let package = package(extract(
+            &PackageJson,
+            &args(&[]),
+            &[(
+                "web/package.json",
+                r#"{"name": "@org/web", "version": "0.1.0", "autoTag": {"enabled": true}}"#,
+            )],
+        ));
+
+        assert_eq!(package.package_name, "@org/web");
+        assert_eq!(package.name, "org__web");
+        assert_eq!(package.version, "0.1.0");
+    }
+
+    #[test]
+    fn deno_json() {
+        assert!(DenoJson.matches(Path::new("mod/deno.json")));
+        assert!(DenoJson.matches(Path::new("mod/deno.jsonc")));
+
+        let package = package(extract(
+            &DenoJson,
+            &args(&[]),
+            &[(
+                "mod/deno.jsonc",
+                "{\n  // comments are allowed\n  \"name\": \"@org/mod\",\n  \
+                 \"version\": \"2.0.0\",\n  \"autoTag\": { \"enabled\": true },\n}\n",
+            )],
+        ));
+
+        assert_eq!(package.package_name, "@org/mod");
+        assert_eq!(package.version, "2.0.0");
+    }
+
+    #[test]
+    fn pyproject_toml() {
+        assert!(PyprojectToml.matches(Path::new("py/pyproject.toml")));
+        assert!(!PyprojectToml.matches(Path::new("py/setup.cfg")));
+
+        let package = package(extract(
+            &PyprojectToml,
+            &args(&[]),
+            &[(
+                "py/pyproject.toml",
+                "[project]\nname = \"My_Package\"\nversion = \"0.3.0\"\n\n\
+                 [tool.auto-tag]\nenabled = true\n",
+            )],
+        ));
+
+        assert_eq!(package.package_name, "My_Package");
+        assert_eq!(package.name, "my-package");
+        assert_eq!(package.version, "0.3.0");
+    }
+
+    #[test]
+    fn detector_matches() {
+        let detector = Detector::new(Path::new("detect"), "*.mod").unwrap();
+        assert!(detector.matches(Path::new("a/b/x.mod")));
+        assert!(!detector.matches(Path::new("a/b/x.toml")));
+
+        let detector = Detector::new(Path::new("detect"), "modules/*.mod").unwrap();
+        assert!(detector.matches(Path::new("./modules/x.mod")));
+        assert!(!detector.matches(Path::new("other/x.mod")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detector_extract() {
+        // `cat -` prints the contents of the file it gets on its input.
+        let detector = Detector::new(Path::new("cat"), "-").unwrap();
+
+        let package = package(extract(
+            &detector,
+            &args(&[]),
+            &[(
+                "-",
+                r#"{"name": "mod", "version": "1.0.0", "enabled": true}"#,
+            )],
+        ));
+
+        assert_eq!(package.name, "mod");
+        assert_eq!(package.version, "1.0.0");
+
+        let reason = skipped(extract(
+            &detector,
+            &args(&["--enabled-packages", "other"]),
+            &[(
+                "-",
+                r#"{"name": "mod", "version": "1.0.0", "enabled": true}"#,
+            )],
+        ));
+
+        assert_eq!(reason, "disabled");
+    }
+}
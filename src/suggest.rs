@@ -0,0 +1,148 @@
+//! Suggesting the next version of packages based on conventional commits.
+
+use crate::{
+    find_packages, history, in_tree, latest_release, scope_packages, target_commit, AutoTagArgs,
+    OutputFormat, SearchArgs,
+};
+use git2::{Commit, Repository};
+
+/// The kind of version bump implied by commits.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Bump {
+    None,
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    /// Returns the bump implied by a conventional commit message.
+    ///
+    /// Breaking changes are major bumps, features are minor bumps, fixes
+    /// and performance improvements are patch bumps, other commits
+    /// do not require a release.
+    pub(crate) fn from_message(message: &str) -> Self {
+        let subject = message.lines().next().unwrap_or_default();
+
+        let (header, _) = match subject.split_once(':') {
+            Some(parts) => parts,
+            None => return Bump::None,
+        };
+
+        let breaking = header.ends_with('!')
+            || message.lines().any(|line| {
+                line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+            });
+
+        if breaking {
+            return Bump::Major;
+        }
+
+        let kind = header
+            .split_once('(')
+            .map_or(header, |(kind, _)| kind)
+            .trim();
+
+        match kind {
+            "feat" => Bump::Minor,
+            "fix" | "perf" => Bump::Patch,
+            _ => Bump::None,
+        }
+    }
+
+    fn apply(self, version: &semver::Version) -> semver::Version {
+        match self {
+            Bump::None => version.clone(),
+            Bump::Patch => semver::Version::new(version.major, version.minor, version.patch + 1),
+            Bump::Minor => semver::Version::new(version.major, version.minor + 1, 0),
+            Bump::Major => semver::Version::new(version.major + 1, 0, 0),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Suggestion {
+    name: String,
+    manifest_version: String,
+    latest_tag_version: Option<String>,
+    bump: Bump,
+    suggested_version: String,
+}
+
+/// Prints the suggested next version of every package, based on the
+/// conventional commits changing the package since its latest release.
+pub(crate) fn suggest(
+    args: &AutoTagArgs,
+    suggest_args: &SearchArgs,
+    repo: &Repository,
+) -> Result<(), anyhow::Error> {
+    let target = target_commit(args, repo)?.id();
+    let mut suggestions = Vec::new();
+
+    let packages = find_packages(args, repo, &suggest_args.paths)?.packages;
+    let scopes = scope_packages(args, &packages)?;
+
+    for package in packages {
+        let latest = latest_release(args, repo, &package)?;
+
+        let suggestion = match latest {
+            Some((version, tag)) => {
+                let since = repo.revparse_single(&tag)?.peel_to_commit()?.id();
+                let dir = history::package_dir(repo, &package.manifest, in_tree(args, repo))?;
+                let commits = history::package_commits(
+                    repo,
+                    target,
+                    Some(since),
+                    args.first_parent,
+                    dir.as_deref(),
+                    &scopes,
+                    &package.package_name,
+                )?;
+
+                let bump = commits.iter().map(commit_bump).max().unwrap_or(Bump::None);
+
+                Suggestion {
+                    latest_tag_version: Some(version.to_string()),
+                    bump,
+                    suggested_version: bump.apply(&version).to_string(),
+                    name: package.name,
+                    manifest_version: package.version,
+                }
+            }
+            // Without any releases, the current version is released first.
+            None => Suggestion {
+                latest_tag_version: None,
+                bump: Bump::None,
+                suggested_version: package.version.clone(),
+                name: package.name,
+                manifest_version: package.version,
+            },
+        };
+
+        suggestions.push(suggestion);
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            for suggestion in &suggestions {
+                args.print_output(format!(
+                    "{}: manifest {}, latest tag {}, suggested {}",
+                    suggestion.name,
+                    suggestion.manifest_version,
+                    suggestion.latest_tag_version.as_deref().unwrap_or("none"),
+                    suggestion.suggested_version,
+                ))?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Csv => {
+            args.print_data(&suggestions)?
+        }
+    }
+
+    Ok(())
+}
+
+fn commit_bump(commit: &Commit) -> Bump {
+    Bump::from_message(commit.message().unwrap_or_default())
+}
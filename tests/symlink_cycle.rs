@@ -0,0 +1,78 @@
+//! Searching a repository containing a symlink to one of its ancestors.
+
+#![cfg(unix)]
+
+use std::path::Path;
+use std::process::Command;
+
+/// Creates a repository with a single commit and an enabled package in `a`
+/// that contains a symlink back to the root.
+fn repository_with_cycle(root: &Path) {
+    let repo = git2::Repository::init(root).unwrap();
+
+    std::fs::create_dir(root.join("a")).unwrap();
+    std::fs::write(
+        root.join("a/Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n\
+         [package.metadata.auto-tag]\nenabled = true\n",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("..", root.join("a/loop")).unwrap();
+
+    let tree = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree).unwrap();
+    let signature = git2::Signature::now("a", "a@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+}
+
+fn run(root: &Path, paths: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_auto-tag"))
+        .current_dir(root)
+        .args(["--dry-run", "--git-user-name", "a", "--git-user-email", "b"])
+        .args(paths)
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}{stderr}");
+
+    format!("{stdout}{stderr}")
+}
+
+#[test]
+fn symlink_cycle_is_not_followed() {
+    let root = tempfile::tempdir().unwrap();
+    repository_with_cycle(root.path());
+
+    let output = run(root.path(), &["."]);
+
+    assert_eq!(
+        output
+            .matches(r#"would create tag "release-a-0.1.0""#)
+            .count(),
+        1
+    );
+    assert!(!output.contains("already searched"), "{output}");
+}
+
+#[test]
+fn symlink_cycle_is_searched_once() {
+    let root = tempfile::tempdir().unwrap();
+    repository_with_cycle(root.path());
+
+    // The symlink leads back to the root, which is already searched.
+    let output = run(root.path(), &[".", "a/loop"]);
+
+    assert_eq!(
+        output
+            .matches(r#"would create tag "release-a-0.1.0""#)
+            .count(),
+        1
+    );
+    assert_eq!(
+        output.matches("the directory was already searched").count(),
+        1
+    );
+}